@@ -1,11 +1,15 @@
 mod player;
 mod game_world;
 mod physics;
+mod physics_query;
+#[cfg(debug_assertions)]
+mod snapshot;
+#[cfg(debug_assertions)]
+mod debug_draw;
 mod walls;
 mod enemy;
 mod weapon;
 
-use bevy::ecs::schedule::{LogLevel, ScheduleBuildSettings};
 use bevy::prelude::*;
 
 use crate::prelude::*;
@@ -13,6 +17,10 @@ use self::player::*;
 use self::enemy::*;
 use self::game_world::*;
 use self::physics::*;
+#[cfg(debug_assertions)]
+use self::snapshot::*;
+#[cfg(debug_assertions)]
+use self::debug_draw::*;
 use self::walls::*;
 use self::weapon::*;
 
@@ -26,54 +34,24 @@ impl Plugin for LevelPlugin {
             //.add_event::<AccelerateEntityEvent>()
             //.add_event::<VelocitateEntityEvent>()
 
-            .insert_resource(FixedTime::new_from_secs(PHYSICS_TIME_STEP)) // set the time step for the CorSchedulei
-            // TODO i think we can avoid chaining everythign some stuff can be in parallel
-            .add_systems((apply_acceleration_adjustments, apply_gravity, apply_resistance, apply_friction).in_set(PhysicsSet::ApplyForces)
+            .add_plugin(PhysicsPlugin)
+            .add_system(follow_platform_paths.after(apply_velocity_override).in_set(PhysicsSet::OverrideVelocity)
                 .in_schedule(CoreSchedule::FixedUpdate))
-            .add_system(apply_acceleration_override.in_set(PhysicsSet::OverrideAcceleration).in_schedule(CoreSchedule::FixedUpdate))
-            .add_systems((apply_accel, apply_velocity_adjustments).in_set(PhysicsSet::ApplyAcceleration)
+            .add_system(carry_riders.after(apply_velocity).in_set(PhysicsSet::ApplyVelocity)
                 .in_schedule(CoreSchedule::FixedUpdate))
-            .add_systems((apply_velocity_override, apply_angular_velocity_override).in_set(PhysicsSet::OverrideVelocity)
-                    .in_schedule(CoreSchedule::FixedUpdate))
-            .add_system(handle_wall_collisions.in_set(PhysicsSet::CastedCollisionDetection)
+            .add_system(respawn_player.after(report_triggers).after(resolve_body_collisions).in_set(PhysicsSet::CollisionDetection)
                 .in_schedule(CoreSchedule::FixedUpdate))
-            .add_systems((apply_velocity, apply_angular_velocity).in_set(PhysicsSet::ApplyVelocity)
+            // the timers count physics ticks so they have to tick with them
+            .add_systems((tick_jump_times, tick_attack_times, tick_drop_through).in_set(PhysicsSet::CollisionDetection)
                 .in_schedule(CoreSchedule::FixedUpdate))
-            .add_systems((apply_position_to_transform,apply_rotation_to_transform, propagate_transform).in_set(PhysicsSet::ModifyTransform)
-                .in_schedule(CoreSchedule::FixedUpdate))
-            .add_system(narrow_phase.in_set(PhysicsSet::CollisionDetection))
-            .configure_set(PhysicsSet::ApplyForces.before(PhysicsSet::OverrideAcceleration))
-            .configure_set(PhysicsSet::OverrideAcceleration.before(PhysicsSet::ApplyAcceleration))
-            .configure_set(PhysicsSet::ApplyAcceleration.before(PhysicsSet::OverrideVelocity))
-            .configure_set(PhysicsSet::OverrideVelocity.before(PhysicsSet::CastedCollisionDetection))
-            .configure_set(PhysicsSet::CastedCollisionDetection.before(PhysicsSet::ApplyVelocity))
-            .configure_set(PhysicsSet::ApplyVelocity.before(PhysicsSet::ModifyTransform))
-            .configure_set(PhysicsSet::ModifyTransform.before(PhysicsSet::CollisionDetection))
-            .edit_schedule(CoreSchedule::FixedUpdate, |schedule| {
-                schedule.set_build_settings(ScheduleBuildSettings {
-                    ambiguity_detection: LogLevel::Warn,
-                    ..default()
-                });
-            })
-            /* .configure_sets((PhysicsSet::ApplyForces, PhysicsSet::OverrideAcceleration, PhysicsSet::ApplyAcceleration, 
-                PhysicsSet::OverrideVelocity, PhysicsSet::CastedCollisionDetection, PhysicsSet::ApplyVelocity, 
-                PhysicsSet::ModifyTransform, PhysicsSet::CollisionDetection).chain())*/
-
-            // we should be using the above code to schedule our physics system but it doesn't work
-            // the acceleartion sometimes happens after casting which breaks teh whole thing
-            /* .add_systems((apply_acceleration_adjustments, apply_gravity, apply_resistance, apply_friction, apply_acceleration_override, // apply forces (aka set aceeleration)
-                apply_velocity_adjustments, apply_accel, apply_velocity_override, apply_angular_velocity_override,// apply acceleration (aka set velocity)
-                handle_wall_collisions, // hanlde casting for collisions
-                apply_velocity, apply_angular_velocity, //apply velocity (aka set positions) 
-                apply_position_to_transform, apply_rotation_to_transform, 
-                propagate_transform, // modify transforms
-                narrow_phase).chain().in_set(PhysicsSet::CollisionDection).in_schedule(CoreSchedule::FixedUpdate))*/
-                // TODO the issue is that there are just to many things in the chain i need to break it up apparently
-            .add_systems((tick_jump_times, tick_attack_times).in_set(PhysicsSet::CollisionDetection))
             .add_system(cleanup_level.in_schedule(OnExit(GlimpseState::GameRunning)));
 
         #[cfg(debug_assertions)]
-        app.add_system(debug_player.in_set(OnUpdate(GlimpseState::GameRunning)));
+        app.add_system(debug_player.in_set(OnUpdate(GlimpseState::GameRunning)))
+            .add_system(debug_snapshot.in_set(OnUpdate(GlimpseState::GameRunning)))
+            .init_resource::<PhysicsDebugDraw>()
+            .init_resource::<DebugLines>()
+            .add_systems((toggle_physics_debug_draw, draw_physics_debug, place_debug_lines).chain().in_set(OnUpdate(GlimpseState::GameRunning)));
     }
 }
 
@@ -84,42 +62,83 @@ enum LevelState {
     Level1,
 }
 
-fn setup_level(mut commands: Commands, query: Query<Entity, With<GlimpseWindow>>) {
+fn setup_level(
+    mut commands: Commands, 
+    query: Query<Entity, With<GlimpseWindow>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
     println!("setting up level start");
     let world = commands.spawn(GameWorldInfo::new(Vec2{x:0.0,y:0.0}, 1.0)).id();
     let player = commands.spawn(PlayerBundle::new(Vec2 {x:0.0, y:5.0}, Vec2 {x:1.0, y:1.7})).id();
     let enemy = commands.spawn(EnemyBundle::new(Vec2 {x:-1.0, y:5.0}, Vec2 {x:1.0, y:1.7})).id();
-    let baby = commands.spawn(
-            SpriteBundle {
-            transform: Transform {
-                translation: Vec3 { x: 1.0, y: 1.0, z: 0.0 },
-                scale: Vec3 { x: 1.0, y: 1.0, z: 1.0 },
-                ..default()
-            },
-            sprite: Sprite {
-                color: DEBUG_COLOR,
-                custom_size: Some(Vec2::new(0.3, 1.0)),
-                ..default()
-            },
-            ..default()}
-        ).id();
+    // shows where the players children end up, only while the debug draw is on
+    #[cfg(debug_assertions)]
+    {
+        let baby = commands.spawn((DebugSprite,
+                SpriteBundle {
+                transform: Transform {
+                    translation: Vec3 { x: 1.0, y: 1.0, z: 0.0 },
+                    scale: Vec3 { x: 1.0, y: 1.0, z: 1.0 },
+                    ..default()
+                },
+                sprite: Sprite {
+                    color: DEBUG_COLOR,
+                    custom_size: Some(Vec2::new(0.3, 1.0)),
+                    ..default()
+                },
+                visibility: Visibility::Hidden,
+                ..default()}
+            )).id();
+        commands.entity(player).push_children(&[baby]);
+    }
     let wall = commands.spawn(WallBundle::new(Vec2 {x: -3.0, y:-8.0}, Vec2 {x:20.0 , y:0.5})).id();
-    let wall1 = commands.spawn(WallBundle::new(Vec2 {x: -6.0, y:1.0}, Vec2 {x:0.5 , y:8.5})).id();
+    // sticky so you can slide down it slowly
+    let wall1 = commands.spawn((WallBundle::new(Vec2 {x: -6.0, y:1.0}, Vec2 {x:0.5 , y:8.5}), SurfaceMaterial::STICKY)).id();
     let wall2 = commands.spawn(WallBundle::new(Vec2 {x: 3.0, y:0.0}, Vec2 {x:0.5 , y:7.5})).id();
-    let wall3 = commands.spawn(WallBundle::new(Vec2 {x: 7.0, y:-3.0}, Vec2 {x:4.0 , y:0.5})).id();
-    let wall4 = commands.spawn(WallBundle::new(Vec2 {x: -11.0, y:-4.0}, Vec2 {x:5.0 , y:0.5})).id();
-    let wall5 = commands.spawn(WallBundle::new(Vec2 {x: -14.0, y:0.0}, Vec2 {x:5.0 , y:0.5})).id();
-    let wall6 = commands.spawn(WallBundle::new(Vec2 {x: -14.0, y:2.0}, Vec2 {x:1.0 , y:0.5})).id();
+    // a conveyor belt back towards the middle
+    let wall3 = commands.spawn((WallBundle::new(Vec2 {x: 7.0, y:-3.0}, Vec2 {x:4.0 , y:0.5}), SurfaceVelocity(-2.0))).id();
+    let wall4 = commands.spawn((WallBundle::new(Vec2 {x: -11.0, y:-4.0}, Vec2 {x:5.0 , y:0.5}), SurfaceMaterial::ICE)).id();
+    let wall5 = commands.spawn((WallBundle::new(Vec2 {x: -14.0, y:0.0}, Vec2 {x:5.0 , y:0.5}), SurfaceMaterial::BOUNCE_PAD)).id();
+    let wall6 = commands.spawn((WallBundle::new(Vec2 {x: -14.0, y:2.0}, Vec2 {x:1.0 , y:0.5}), SurfaceMaterial::MUD)).id();
     let wall7 = commands.spawn(WallBundle::new(Vec2 {x: -16.0, y:4.0}, Vec2 {x:1.0 , y:0.5})).id();
     let wall8 = commands.spawn(WallBundle::new(Vec2 {x: -18.0, y:6.0}, Vec2 {x:1.0 , y:0.5})).id();
     let wall9 = commands.spawn(WallBundle::new(Vec2 {x: -20.0, y:8.0}, Vec2 {x:1.0 , y:0.5})).id();
+    let ramp = commands.spawn(PolyWallBundle::new(Vec2 {x: -9.0, y:-7.0}, 
+        vec![Vec2 {x: -1.5, y:-0.75}, Vec2 {x: 1.5, y:-0.75}, Vec2 {x: 1.5, y:0.75}],
+        &mut meshes, &mut materials)).id();
+    let one_way = commands.spawn(OneWayPlatformBundle::new(Vec2 {x: -1.0, y:-2.0}, Vec2 {x:3.0 , y:0.25})).id();
+    let platform = commands.spawn(MovingPlatformBundle::new(
+        Vec2 {x: -4.0, y:-5.5}, vec![Vec2 {x: 1.0, y:-5.5}], Vec2 {x:2.0 , y:0.5}, 2.0)).id();
+    // floaty up the stairs on the left
+    let low_gravity = commands.spawn(GravityZoneBundle::new(Vec2 {x: -17.0, y:6.0}, Shape::Rect(Vec2 {x:8.0, y:8.0}),
+        GravityMode::Override(GRAVITY_VECTOR * 0.4))).id();
+    // a chain hanging from the ceiling on the right with a weight on the end to knock around
+    let link = Vec2 {x:0.2, y:1.0};
+    let link1 = commands.spawn(ChainLinkBundle::new(Vec2 {x: 5.5, y:2.5}, link,
+        Joint::to_world(Vec2 {x: 5.5, y:3.5}, JointKind::Distance(0.5)).with_anchors(Vec2 {x: 0.0, y:0.5}, Vec2 {x: 5.5, y:3.5}))).id();
+    let link2 = commands.spawn(ChainLinkBundle::new(Vec2 {x: 5.5, y:1.5}, link,
+        Joint::new(link1, JointKind::Revolute).with_anchors(Vec2 {x: 0.0, y:0.5}, Vec2 {x: 0.0, y:-0.5}))).id();
+    let weight = commands.spawn((ChainLinkBundle::new(Vec2 {x: 5.5, y:0.45}, Vec2 {x:0.6, y:0.6},
+        Joint::new(link2, JointKind::Rope(0.25)).with_anchors(Vec2 {x: 0.0, y:0.3}, Vec2 {x: 0.0, y:-0.5})), Collider)).id();
+    // and a block bobbing on a spring over the ramp
+    let bobber = commands.spawn((ChainLinkBundle::new(Vec2 {x: -9.5, y:0.0}, Vec2 {x:0.6, y:0.6},
+        Joint::to_world(Vec2 {x: -9.5, y:3.0}, JointKind::Spring { rest_length: 2.5, stiffness: 30.0, damping: 2.0 })
+            .with_anchors(Vec2 {x: 0.0, y:0.3}, Vec2 {x: -9.5, y:3.0})), Collider)).id();
+    // falling off the bottom of the level puts the player back at the start
+    let kill_zone = commands.spawn((TriggerBundle::new(Vec2 {x: 0.0, y:-20.0}, Shape::Rect(Vec2 {x:100.0, y:4.0}), PLAYER_LAYER),
+        KillZone { respawn: Vec2 {x:0.0, y:5.0} })).id();
+    // a pool on the floor to swim in
+    let pool = commands.spawn(FluidVolumeBundle::new(Vec2 {x: 4.5, y:-6.75}, Vec2 {x:4.0, y:2.0}, FluidVolume::default())).id();
+    // and an updraft off the end of the floor on the right that holds you up under the ledge
+    let updraft = commands.spawn(GravityZoneBundle::new(Vec2 {x: 8.5, y:-5.0}, Shape::Rect(Vec2 {x:3.0, y:5.0}),
+        GravityMode::Add(Vec2 {x: 0.0, y:14.0}))).id();
 
     let window = query.get_single().unwrap();
     commands.entity(window).push_children(&[world]);
-    commands.entity(player).push_children(&[baby]);
     commands.entity(world).push_children(&[player]);
     commands.entity(world).push_children(&[enemy]);
-    commands.entity(world).push_children(&[wall, wall1, wall2, wall3, wall4, wall5, wall6, wall7, wall8, wall9]);
+    commands.entity(world).push_children(&[wall, wall1, wall2, wall3, wall4, wall5, wall6, wall7, wall8, wall9, ramp, one_way, platform, low_gravity, updraft, pool, link1, link2, weight, bobber, kill_zone]);
     println!("setting up level end");
 }
 
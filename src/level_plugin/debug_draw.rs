@@ -0,0 +1,205 @@
+use bevy::prelude::*;
+
+use super::physics::*;
+use super::game_world::GameWorld;
+
+// how thick the lines are in meters
+const DEBUG_LINE_WIDTH: f32 = 0.04;
+// in front of everything else in the world
+const DEBUG_LINE_Z: f32 = 10.0;
+// circles get drawn as this many sided polygons
+const DEBUG_CIRCLE_SIDES: usize = 16;
+// the arrows are this many seconds of velocity and this many seconds squared of acceleration long
+const DEBUG_VELOCITY_SCALE: f32 = 0.25;
+const DEBUG_ACCELERATION_SCALE: f32 = 0.05;
+// how big the crosses on contact points are
+const DEBUG_POINT_SIZE: f32 = 0.15;
+
+const SHAPE_COLOR: Color = Color::rgb(0.2, 0.9, 0.9);
+const SLEEPING_COLOR: Color = Color::rgb(0.3, 0.3, 0.6);
+const VELOCITY_COLOR: Color = Color::rgb(0.2, 0.9, 0.2);
+const ACCELERATION_COLOR: Color = Color::rgb(0.9, 0.9, 0.2);
+const SENSOR_COLOR: Color = Color::rgb(1.0, 0.3, 0.9);
+const CAST_COLOR: Color = Color::rgba(1.0, 0.6, 0.2, 0.6);
+const CONTACT_COLOR: Color = Color::rgb(1.0, 0.1, 0.1);
+
+// F3 turns it on and off
+#[derive(Resource, Default)]
+pub struct PhysicsDebugDraw {
+    pub enabled: bool,
+}
+
+// the line sprites stick around and get reused every frame, the ones we don't need get hidden
+#[derive(Component, Default)]
+pub struct DebugLine;
+
+// a sprite that only shows while the debug draw is on
+#[derive(Component, Default)]
+pub struct DebugSprite;
+
+pub fn toggle_physics_debug_draw(
+    mut commands: Commands,
+    keyboard_input: Res<Input<KeyCode>>,
+    mut debug_draw: ResMut<PhysicsDebugDraw>,
+    mut sprite_query: Query<&mut Visibility, With<DebugSprite>>,
+) {
+    if keyboard_input.just_pressed(KeyCode::F3) {
+        debug_draw.enabled = !debug_draw.enabled;
+        // handle_wall_collisions only keeps its casts around while this is in
+        if debug_draw.enabled {
+            commands.init_resource::<WallCasts>();
+        } else {
+            commands.remove_resource::<WallCasts>();
+        }
+    }
+    let visibility = if debug_draw.enabled { Visibility::Visible } else { Visibility::Hidden };
+    for mut sprite_visibility in sprite_query.iter_mut() {
+        sprite_visibility.set_if_neq(visibility);
+    }
+}
+
+pub struct Line {
+    start: Vec2,
+    end: Vec2,
+    color: Color,
+}
+
+// what draw_physics_debug wants drawn this frame
+#[derive(Resource, Default)]
+pub struct DebugLines(Vec<Line>);
+
+fn add_line(lines: &mut Vec<Line>, start: Vec2, end: Vec2, color: Color) {
+    if start.distance(end) >= MU {
+        lines.push(Line { start, end, color });
+    }
+}
+
+fn add_loop(lines: &mut Vec<Line>, points: &[Vec2], color: Color) {
+    for (start, end) in points.iter().zip(points.iter().cycle().skip(1)) {
+        add_line(lines, *start, *end, color);
+    }
+}
+
+// the lines are sprites under the world so they use the same meters as everything else
+fn place_line(line: &Line, transform: &mut Transform, sprite: &mut Sprite) {
+    let delta = line.end - line.start;
+    transform.translation = ((line.start + line.end) * 0.5).extend(DEBUG_LINE_Z);
+    transform.rotation = Quat::from_rotation_z(delta.y.atan2(delta.x));
+    sprite.color = line.color;
+    sprite.custom_size = Some(Vec2::new(delta.length(), DEBUG_LINE_WIDTH));
+}
+
+fn outline(pos: &Vec2, shape: &Shape, angle: f32) -> Vec<Vec2> {
+    match shape {
+        Shape::Circle(radius) => (0..DEBUG_CIRCLE_SIDES)
+            .map(|i| *pos + Vec2::from_angle(angle + std::f32::consts::TAU * i as f32 / DEBUG_CIRCLE_SIDES as f32) * *radius)
+            .collect(),
+        _ => shape_points(pos, shape, angle),
+    }
+}
+
+type DebugBodies<'w, 's> = Query<'w, 's,
+    (&'static GlobalPosition, &'static GlobalRotation, Option<&'static Shape>, Option<&'static Velocity>,
+        Option<&'static Acceleration>, Option<&'static WallSensor>, Option<&'static Sleeping>)>;
+
+// works out every line for the current physics state
+pub fn draw_physics_debug(
+    debug_draw: Res<PhysicsDebugDraw>,
+    mut lines: ResMut<DebugLines>,
+    body_query: DebugBodies,
+    // walls don't get a global position so they go by their own
+    wall_query: Query<(&Position, Option<&Rotation>, &Shape), With<Wall>>,
+    casts: Option<Res<WallCasts>>,
+    colliding: Res<CollidingPairs>,
+) {
+    let lines = &mut lines.0;
+    lines.clear();
+    if debug_draw.enabled {
+        for (pos, rot, shape) in wall_query.iter() {
+            add_loop(lines, &outline(&pos.0, shape, rot.map_or(0.0, |rot| rot.0)), SHAPE_COLOR);
+        }
+
+        for (pos, rot, shape, vel, accel, sensor, sleeping) in body_query.iter() {
+            if let Some(shape) = shape {
+                let color = if sleeping.is_some() { SLEEPING_COLOR } else { SHAPE_COLOR };
+                add_loop(lines, &outline(&pos.0, shape, rot.0), color);
+                // light up the sides of the box around it that are touching a wall
+                if let Some(sensor) = sensor {
+                    let aabb = Aabb::from_shape(&pos.0, shape, rot.0);
+                    if sensor.left {
+                        add_line(lines, aabb.min, Vec2::new(aabb.min.x, aabb.max.y), SENSOR_COLOR);
+                    }
+                    if sensor.right {
+                        add_line(lines, Vec2::new(aabb.max.x, aabb.min.y), aabb.max, SENSOR_COLOR);
+                    }
+                    if sensor.down {
+                        add_line(lines, aabb.min, Vec2::new(aabb.max.x, aabb.min.y), SENSOR_COLOR);
+                    }
+                }
+            }
+            if let Some(vel) = vel {
+                add_line(lines, pos.0, pos.0 + vel.0 * DEBUG_VELOCITY_SCALE, VELOCITY_COLOR);
+            }
+            if let Some(accel) = accel {
+                add_line(lines, pos.0, pos.0 + accel.0 * DEBUG_ACCELERATION_SCALE, ACCELERATION_COLOR);
+            }
+        }
+
+        // where each sweep started, where it stopped and the corners joined up between them
+        if let Some(casts) = casts {
+            for cast in casts.0.iter() {
+                let start = outline(&cast.position, &cast.shape, cast.angle);
+                let end_pos = cast.position + cast.motion * cast.time.unwrap_or(1.0);
+                let end = outline(&end_pos, &cast.shape, cast.angle);
+                add_loop(lines, &start, CAST_COLOR);
+                add_loop(lines, &end, CAST_COLOR);
+                for (start, end) in start.iter().zip(end.iter()) {
+                    add_line(lines, *start, *end, CAST_COLOR);
+                }
+            }
+        }
+
+        // a cross on every contact point and a line along the normal
+        for (_, contact) in colliding.sorted() {
+            for point in contact.points.iter() {
+                let half = DEBUG_POINT_SIZE * 0.5;
+                add_line(lines, *point - Vec2::splat(half), *point + Vec2::splat(half), CONTACT_COLOR);
+                add_line(lines, *point + Vec2::new(-half, half), *point + Vec2::new(half, -half), CONTACT_COLOR);
+                add_line(lines, *point, *point + contact.normal * contact.depth.max(DEBUG_POINT_SIZE), CONTACT_COLOR);
+            }
+        }
+    }
+
+}
+
+// moves the line sprites onto the lines, we reuse the sprites we already have
+// and only spawn more when there are more lines than ever before
+pub fn place_debug_lines(
+    mut commands: Commands,
+    lines: Res<DebugLines>,
+    mut line_query: Query<(&mut Transform, &mut Sprite, &mut Visibility), With<DebugLine>>,
+    world_query: Query<Entity, With<GameWorld>>,
+) {
+    let mut lines = lines.0.iter();
+    for (mut transform, mut sprite, mut visibility) in line_query.iter_mut() {
+        match lines.next() {
+            Some(line) => {
+                place_line(line, &mut transform, &mut sprite);
+                visibility.set_if_neq(Visibility::Visible);
+            }
+            None => {
+                visibility.set_if_neq(Visibility::Hidden);
+            }
+        }
+    }
+    let Ok(world) = world_query.get_single() else {
+        return;
+    };
+    for line in lines {
+        let mut transform = Transform::default();
+        let mut sprite = Sprite::default();
+        place_line(line, &mut transform, &mut sprite);
+        let line = commands.spawn((DebugLine, SpriteBundle { transform, sprite, ..default() })).id();
+        commands.entity(world).add_child(line);
+    }
+}
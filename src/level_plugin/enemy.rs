@@ -2,7 +2,6 @@
 use bevy::prelude::*;
 
 use super::physics::*;
-use super::walls::*;
 use super::player::*;
 
 #[derive(Component, Default)]
@@ -22,7 +21,11 @@ pub struct EnemyBundle {
     pub gravity: Gravity,
     pub wall_collider: WallCollider,
     pub wall_sensor: WallSensor,
-    pub collider: Collider
+    pub slope_walker: SlopeWalker,
+    pub submerged: Submerged,
+    pub can_sleep: CanSleep,
+    pub collider: Collider,
+    pub collision_groups: CollisionGroups
 }
 
 impl EnemyBundle {
@@ -51,6 +54,7 @@ impl EnemyBundle {
                 },
                 ..default()
             },
+            collision_groups: CollisionGroups::new(ENEMY_LAYER, PLAYER_LAYER | PLAYER_WEAPON_LAYER | TRIGGER_LAYER),
             ..default()
         }
     }
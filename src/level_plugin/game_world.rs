@@ -40,45 +40,6 @@ impl GameWorldInfo {
     }
 }
 
-// update our 2d tranforms starting from the world transform
-pub fn propagate_transform(
-    world_query: Query<Entity, With<GameWorld>>, 
-    child_query: Query<&Children, With<TwoDimTrans>>,
-    mut transform_query: Query<(&mut TwoDimTrans, &mut GlobalRotation, &mut GlobalPosition, &Position, &Rotation)>
-) {
-    // stack to use for DFS down the transform tree
-    let mut stack = vec![world_query.single()];
-    while stack.len() > 0 {
-
-        // get the top element of the stack extract the value we need from it
-        let cur = stack.pop().unwrap();
-        if let Ok(children) = child_query.get(cur) {
-            let (trans, rot, _, _, _) = transform_query.get(cur).unwrap();
-            let trans = trans.clone();
-            let rot = rot.0;
-
-            for child in children.iter() {
-                // get the child transform values
-                if let Ok((mut child_trans, mut child_rot, 
-                    mut child_pos, local_pos, local_rot)) 
-                    = transform_query.get_mut(*child) {
-                    // generate the local transform
-                    child_trans.0 = Mat3::from_scale_angle_translation(Vec2::ONE, local_rot.0, local_pos.0);
-                    // multiply the teh parent tranfrom
-                    child_trans.0 = trans.0*child_trans.0;
-                    // tranform the local point by the child point
-                    child_pos.0 = trans.0.transform_point2(local_pos.0);
-                    // transform the local rotation
-                    child_rot.0 = rot+local_rot.0;
-                    // add the child to the stack
-                    stack.push(*child);
-                    
-                }
-            }
-        }
-    }
-}
-
 /*
 heres is our orginization
 file for eahc enitity named after the entiyt contains components and entity specifc systems
@@ -1,50 +1,269 @@
-// TODO break this into a plugin
+use bevy::ecs::schedule::{LogLevel, ScheduleBuildSettings};
 use bevy::prelude::*;
 use bevy::utils::Duration;
+use bevy::utils::{HashMap, HashSet};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+mod joints;
+pub use self::joints::*;
 
 #[derive(SystemSet, Debug, Hash, PartialEq, Eq, Clone)]
 pub enum PhysicsSet {
+    StoreState,
     ApplyForces,
     OverrideAcceleration,
     ApplyAcceleration,
     OverrideVelocity,
+    SolveJoints,
     CastedCollisionDetection,
     ApplyVelocity,
     ModifyTransform,
-    CollisionDetection
+    CollisionDetection,
+    Checksum
 }
 
+// the defaults for PhysicsConfig, MU is also what the collision math treats as zero
 pub const PHYSICS_TIME_STEP: f32 = 1.0 / 300.0;
 pub const MU: f32 = 0.0000003;
+pub const GRAVITY_VECTOR: Vec2 = Vec2 { x:0.0, y:-9.8 };
+// about a screen and a half, the screen is 40 across
+pub const ACTIVE_RANGE: f32 = 60.0;
+pub const SLEEP_SPEED: f32 = 0.05;
+pub const SLEEP_TICKS: u32 = 150;
+// a tenth of a millimeter, big enough to still be there after rounding anywhere in the level
+pub const SKIN_WIDTH: f32 = 0.0001;
+
+// the physics settings that can change while the game is running
+#[derive(Resource, Debug, Clone)]
+pub struct PhysicsConfig {
+    // seconds per physics tick
+    pub time_step: f32,
+    pub gravity: Vec2,
+    // speeds and distances smaller than this count as zero
+    pub epsilon: f32,
+    // how far wall colliders get kept off the walls so they don't end up exactly touching them
+    pub skin_width: f32,
+    // bodies that can sleep go inactive when they're further than this from every ActiveRangeAnchor
+    pub active_range: f32,
+    // or when they've been moving slower than sleep_speed for sleep_ticks ticks in a row
+    pub sleep_speed: f32,
+    pub sleep_ticks: u32,
+}
 
-#[derive(Component, Default)]
-pub struct Gravity;
+impl Default for PhysicsConfig {
+    fn default() -> Self {
+        PhysicsConfig {
+            time_step: PHYSICS_TIME_STEP,
+            gravity: GRAVITY_VECTOR,
+            epsilon: MU,
+            skin_width: SKIN_WIDTH,
+            active_range: ACTIVE_RANGE,
+            sleep_speed: SLEEP_SPEED,
+            sleep_ticks: SLEEP_TICKS,
+        }
+    }
+}
+
+// runs the physics on the fixed time step, insert a PhysicsConfig before adding this to change the defaults
+// anything else that moves bodies around should go in one of the PhysicsSets in CoreSchedule::FixedUpdate
+pub struct PhysicsPlugin;
+
+impl Plugin for PhysicsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PhysicsConfig>();
+        let time_step = app.world.resource::<PhysicsConfig>().time_step;
+        app.insert_resource(FixedTime::new_from_secs(time_step)) // set the time step for the CorSchedulei
+            .init_resource::<BroadPhasePairs>()
+            .init_resource::<CollidingPairs>()
+            .init_resource::<PhysicsChecksum>()
+            .add_event::<CollisionStarted>()
+            .add_event::<CollisionStayed>()
+            .add_event::<CollisionEnded>()
+            .add_event::<TriggerEntered>()
+            .add_event::<TriggerExited>()
+            .add_system(sync_fixed_time)
+            .add_system(interpolate_transforms)
+            .add_system(store_previous_state.in_set(PhysicsSet::StoreState).in_schedule(CoreSchedule::FixedUpdate))
+            .add_system(store_ccd_poses.in_set(PhysicsSet::StoreState).in_schedule(CoreSchedule::FixedUpdate))
+            .add_system(update_sleep.in_set(PhysicsSet::StoreState).in_schedule(CoreSchedule::FixedUpdate))
+            // TODO i think we can avoid chaining everythign some stuff can be in parallel
+            // these all add to the same acceleration (or velocity) so they're chained, floats added
+            // in a different order don't come out exactly the same and the physics has to be deterministic
+            .add_systems((apply_acceleration_adjustments, apply_gravity, detect_submersion, apply_buoyancy, apply_resistance, apply_friction)
+                .chain().in_set(PhysicsSet::ApplyForces).in_schedule(CoreSchedule::FixedUpdate))
+            .add_system(apply_acceleration_override.in_set(PhysicsSet::OverrideAcceleration).in_schedule(CoreSchedule::FixedUpdate))
+            .add_systems((apply_accel, apply_velocity_adjustments).chain().in_set(PhysicsSet::ApplyAcceleration)
+                .in_schedule(CoreSchedule::FixedUpdate))
+            .add_systems((apply_velocity_override, apply_angular_velocity_override).in_set(PhysicsSet::OverrideVelocity)
+                    .in_schedule(CoreSchedule::FixedUpdate))
+            .add_system(solve_joints.in_set(PhysicsSet::SolveJoints).in_schedule(CoreSchedule::FixedUpdate))
+            .add_system(handle_wall_collisions.in_set(PhysicsSet::CastedCollisionDetection)
+                .in_schedule(CoreSchedule::FixedUpdate))
+            .add_systems((apply_velocity, apply_angular_velocity).in_set(PhysicsSet::ApplyVelocity)
+                .in_schedule(CoreSchedule::FixedUpdate))
+            // both write the Transform so they go one after the other
+            .add_systems((apply_position_to_transform,apply_rotation_to_transform).chain().in_set(PhysicsSet::ModifyTransform)
+                .in_schedule(CoreSchedule::FixedUpdate))
+            .add_system(propagate_transform.in_set(PhysicsSet::ModifyTransform)
+                .in_schedule(CoreSchedule::FixedUpdate))
+            .add_systems((broad_phase, narrow_phase, resolve_body_collisions).chain().in_set(PhysicsSet::CollisionDetection)
+                .in_schedule(CoreSchedule::FixedUpdate))
+            .add_system(report_triggers.after(narrow_phase).in_set(PhysicsSet::CollisionDetection)
+                .in_schedule(CoreSchedule::FixedUpdate))
+            .add_system(update_checksum.in_set(PhysicsSet::Checksum).in_schedule(CoreSchedule::FixedUpdate))
+            // app.configure_set only configures the main schedule, so the sets have to be
+            // chained on the fixed schedule itself or the acceleration can end up after the casting
+            .edit_schedule(CoreSchedule::FixedUpdate, |schedule| {
+                schedule.configure_sets((PhysicsSet::StoreState, PhysicsSet::ApplyForces, PhysicsSet::OverrideAcceleration, PhysicsSet::ApplyAcceleration, 
+                    PhysicsSet::OverrideVelocity, PhysicsSet::SolveJoints, PhysicsSet::CastedCollisionDetection, PhysicsSet::ApplyVelocity, 
+                    PhysicsSet::ModifyTransform, PhysicsSet::CollisionDetection, PhysicsSet::Checksum).chain());
+                schedule.set_build_settings(ScheduleBuildSettings {
+                    ambiguity_detection: LogLevel::Warn,
+                    ..default()
+                });
+            });
+    }
+}
+
+// keep the fixed schedule running at the configured time step
+pub fn sync_fixed_time(config: Res<PhysicsConfig>, mut fixed_time: ResMut<FixedTime>) {
+    if config.is_changed() {
+        fixed_time.period = Duration::from_secs_f32(config.time_step);
+    }
+}
+
+// scale multiplies the gravity the body would feel, 0 floats and 2 falls twice as fast
+// direction turns it to pull that way instead, keeping how strong it is
+#[derive(Component, Debug, Clone, Copy)]
+pub struct Gravity {
+    pub scale: f32,
+    pub direction: Option<Vec2>,
+}
+
+impl Default for Gravity {
+    fn default() -> Self {
+        Gravity { scale: 1.0, direction: None }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GravityMode {
+    // replace the gravity with this one
+    Override(Vec2),
+    // add this on top of the gravity
+    Add(Vec2),
+}
+
+// changes the gravity of bodies whose center is inside the zones shape, zones aren't colliders so
+// nothing bumps into them, if overriding zones overlap the highest priority one wins
+#[derive(Component, Debug, Clone, Copy)]
+pub struct GravityZone {
+    pub mode: GravityMode,
+    pub priority: i32,
+}
+
+#[derive(Bundle)]
+pub struct GravityZoneBundle {
+    pub zone: GravityZone,
+    pub body: Body,
+}
+
+impl GravityZoneBundle {
+    pub fn new(position: Vec2, shape: Shape, mode: GravityMode) -> GravityZoneBundle {
+        GravityZoneBundle {
+            zone: GravityZone { mode, priority: 0 },
+            body: Body {
+                position: Position(position),
+                shape,
+                ..default()
+            },
+        }
+    }
+}
 
 #[derive(Component, Default, Debug)]
 pub struct Resistance(pub Vec2);
 
+const FLUID_COLOR: Color = Color::rgba(0.2, 0.4, 0.9, 0.4);
+
+// water and the like, bodies with a Submerged inside get pushed up by how much of them is under
+// and slowed down more, density is how hard it pushes up compared to gravity per unit of area so
+// a body with the same mass as its area floats at density 1, drag scales the Resistance of bodies in it
+#[derive(Component, Debug, Clone, Copy)]
+pub struct FluidVolume {
+    pub density: f32,
+    pub drag: f32,
+}
+
+impl Default for FluidVolume {
+    fn default() -> Self {
+        FluidVolume { density: 1.0, drag: 4.0 }
+    }
+}
+
+#[derive(Bundle, Default)]
+pub struct FluidVolumeBundle {
+    pub fluid: FluidVolume,
+    pub sprite_bundle: SpriteBundle,
+    pub body: Body,
+}
+
+impl FluidVolumeBundle {
+    pub fn new(position: Vec2, size: Vec2, fluid: FluidVolume) -> FluidVolumeBundle {
+        FluidVolumeBundle {
+            fluid,
+            sprite_bundle: SpriteBundle {
+                transform: Transform {
+                    translation: position.extend(0.0),
+                    ..default()
+                },
+                sprite: Sprite {
+                    color: FLUID_COLOR,
+                    custom_size: Some(size),
+                    ..default()
+                },
+                ..default()
+            },
+            body: Body {
+                position: Position(position),
+                shape: Shape::Rect(size),
+                ..default()
+            },
+        }
+    }
+}
+
+// how far into a fluid a body is, controllers can check this to swim instead of walk
+// fraction is how much of the shape is under, 0 when out of the water and 1 when all the way in
+#[derive(Component, Default, Debug, Clone)]
+pub struct Submerged {
+    pub fraction: f32,
+    pub fluid: Option<Entity>,
+}
+
 #[derive(Component, Default, Debug)]
 pub struct Friction(pub Vec2);
 
-#[derive(Component, Default, Debug)]
+#[derive(Component, Default, Debug, Clone)]
 pub struct Velocity(pub Vec2);
 
-#[derive(Component, Default, Debug)]
+#[derive(Component, Default, Debug, Clone)]
 pub struct Acceleration(pub Vec2);
 
-#[derive(Component, Default, Debug)]
+#[derive(Component, Default, Debug, Clone)]
 pub struct Position(pub Vec2);
 
-#[derive(Component, Default, Debug)]
+#[derive(Component, Default, Debug, Clone)]
 pub struct Rotation(pub f32);
 
-#[derive(Component, Default, Debug)]
+#[derive(Component, Default, Debug, Clone)]
 pub struct GlobalPosition(pub Vec2);
 
-#[derive(Component, Default, Debug)]
+#[derive(Component, Default, Debug, Clone)]
 pub struct GlobalRotation(pub f32);
 
-#[derive(Component, Default, Debug)]
+#[derive(Component, Default, Debug, Clone)]
 pub struct AngularVelocity(pub f32);
 
 #[derive(Component, Debug, Clone)]
@@ -56,10 +275,11 @@ impl Default for TwoDimTrans {
     }
 }
 
-#[derive(Component)]
+#[derive(Component, Clone)]
 pub enum Shape {
     Rect(Vec2),
     Circle(f32),
+    // the corners relative to the center of the body, has to be convex
     Poly(Vec<Vec2>)
 }
 
@@ -69,9 +289,78 @@ impl Default for Shape {
     }
 }
 
+impl Shape {
+    // a poly needs at least 3 corners to have an inside, anything less never hits anything
+    pub fn is_degenerate(&self) -> bool {
+        matches!(self, Shape::Poly(points) if points.len() < 3)
+    }
+}
+
 #[derive(Component, Default)]
 pub struct Collider;
 
+// the layers a collider can be on, a collider can be on more than one
+pub const PLAYER_LAYER: u32 = 1 << 0;
+pub const ENEMY_LAYER: u32 = 1 << 1;
+pub const PLAYER_WEAPON_LAYER: u32 = 1 << 2;
+pub const ENEMY_WEAPON_LAYER: u32 = 1 << 3;
+pub const PICKUP_LAYER: u32 = 1 << 4;
+pub const TRIGGER_LAYER: u32 = 1 << 5;
+pub const ALL_LAYERS: u32 = u32::MAX;
+
+// memberships are the layers this collider is on, filters are the layers it can hit
+// two colliders are only checked if each one is on a layer the other can hit
+// colliders without this are on and hit every layer
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CollisionGroups {
+    pub memberships: u32,
+    pub filters: u32,
+}
+
+impl CollisionGroups {
+    pub fn new(memberships: u32, filters: u32) -> CollisionGroups {
+        CollisionGroups { memberships, filters }
+    }
+
+    pub fn interacts_with(&self, other: &CollisionGroups) -> bool {
+        self.memberships & other.filters != 0 && other.memberships & self.filters != 0
+    }
+}
+
+impl Default for CollisionGroups {
+    fn default() -> Self {
+        CollisionGroups::new(ALL_LAYERS, ALL_LAYERS)
+    }
+}
+
+// bodies with this can go to sleep, sleeping bodies get skipped by the force, velocity and
+// collision systems so they cost next to nothing until something wakes them up
+#[derive(Component, Default, Debug, Clone)]
+pub struct CanSleep {
+    still_ticks: u32,
+}
+
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sleeping {
+    // stopped moving, wakes up when something runs into it or gives it a velocity
+    AtRest,
+    // too far from the action, wakes up once it's back in range
+    OutOfRange,
+}
+
+// bodies near one of these stay awake, put it on whatever the camera follows
+#[derive(Component, Default, Debug)]
+pub struct ActiveRangeAnchor;
+
+// colliders with this get swept from where they were last tick to where they are now
+// instead of only checked where they end up, so fast things like weapons and bullets
+// can't skip over something thin between ticks
+#[derive(Component, Default, Debug, Clone)]
+pub struct Ccd {
+    // global position and rotation as of the end of the last tick, none until we've had one
+    last_pose: Option<(Vec2, f32)>,
+}
+
 #[derive(Bundle, Default)]
 pub struct Body {
     pub position: Position,
@@ -82,6 +371,47 @@ pub struct Body {
     pub shape: Shape
 }
 
+// how hard a body is to push around, colliders with a mass and a velocity push each other apart
+#[derive(Component, Debug, Clone, Copy)]
+pub struct Mass(pub f32);
+
+impl Default for Mass {
+    fn default() -> Self {
+        Mass(1.0)
+    }
+}
+
+// how bouncy a body is, 0 doesn't bounce at all and 1 bounces back at the speed it hit with
+#[derive(Component, Default, Debug, Clone, Copy)]
+pub struct Restitution(pub f32);
+
+// how the transform gets drawn in between physics ticks, physics runs at a fixed rate
+// which usually isn't the frame rate so snapping to the latest tick judders
+#[derive(Component, Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Interpolation {
+    // snap to the latest tick
+    Off,
+    // blend between the last two ticks, always a tick behind but never wrong
+    #[default]
+    Interpolate,
+    // guess ahead from the last two ticks, up to date but can overshoot when things stop or turn
+    Extrapolate,
+}
+
+// the position and rotation as of the start of the last tick
+#[derive(Component, Default, Debug, Clone)]
+pub struct PreviousPosition(pub Vec2);
+
+#[derive(Component, Default, Debug, Clone)]
+pub struct PreviousRotation(pub f32);
+
+#[derive(Bundle, Default)]
+pub struct InterpolationBundle {
+    pub interpolation: Interpolation,
+    pub previous_position: PreviousPosition,
+    pub previous_rotation: PreviousRotation,
+}
+
 #[derive(Bundle, Default)]
 pub struct BasePhysicsBundle {
     pub body: Body,
@@ -90,21 +420,24 @@ pub struct BasePhysicsBundle {
     pub angular_velocity: AngularVelocity,
     pub resistance: Resistance,
     pub friction: Friction,
+    pub mass: Mass,
+    pub restitution: Restitution,
+    pub interpolation: InterpolationBundle,
 }
 
-#[derive(Component, Default, Debug)]
+#[derive(Component, Default, Debug, Clone)]
 pub struct OverrideVelocity(pub Option<f32>, pub Option<f32>);
 
-#[derive(Component, Default, Debug)]
+#[derive(Component, Default, Debug, Clone)]
 pub struct OverrideAcceleration(pub Option<f32>, pub Option<f32>);
 
-#[derive(Component, Default, Debug)]
+#[derive(Component, Default, Debug, Clone)]
 pub struct AdjustVelocity(pub Vec2);
 
-#[derive(Component, Default, Debug)]
+#[derive(Component, Default, Debug, Clone)]
 pub struct AdjustAcceleration(pub Vec2);
 
-#[derive(Component, Default, Debug)]
+#[derive(Component, Default, Debug, Clone)]
 pub struct OverrideAngularVelocity(pub Option<f32>);
 
 // TODO we could do this with events instead I think
@@ -118,7 +451,7 @@ pub struct PhysicsControllerBundle {
 }
 
 
-pub fn apply_acceleration_adjustments(mut query: Query<(&mut Acceleration, &AdjustAcceleration)>) {
+pub fn apply_acceleration_adjustments(mut query: Query<(&mut Acceleration, &AdjustAcceleration), Without<Sleeping>>) {
     eprintln!("apply_acceleration_adjustments");
     for (mut accel, adjust) in query.iter_mut() {
         accel.0 += adjust.0;
@@ -164,304 +497,2373 @@ pub fn apply_angular_velocity_override(mut query: Query<(&mut AngularVelocity, &
         }
     }
 }
-const GRAVITY_VECTOR: Vec2 = Vec2 { x:0.0, y:-9.8 };
-pub fn apply_gravity(mut query: Query<&mut Acceleration, With<Gravity>>) {
+type GravityZones<'w, 's> = Query<'w, 's, (Entity, &'static GravityZone, &'static GlobalPosition, &'static GlobalRotation, &'static Shape)>;
+
+// the gravity a body actually feels, the zones its center is in with its own scale and direction on top
+fn effective_gravity(config: &PhysicsConfig, zone_query: &GravityZones, gravity: &Gravity, pos: Option<&GlobalPosition>) -> Vec2 {
+    // (priority, zone, gravity), same priority goes to the older zone so it doesn't come down to query order
+    let mut override_gravity: Option<(i32, Entity, Vec2)> = None;
+    let mut added = Vec2::ZERO;
+    if let Some(pos) = pos {
+        for (zone_entity, zone, zone_pos, zone_rot, zone_shape) in zone_query.iter() {
+            if !point_in_shape(&pos.0, &zone_pos.0, zone_shape, zone_rot.0) {
+                continue;
+            }
+            match zone.mode {
+                GravityMode::Override(zone_gravity) => {
+                    if override_gravity.is_none_or(|(priority, entity, _)| (zone.priority, entity) > (priority, zone_entity)) {
+                        override_gravity = Some((zone.priority, zone_entity, zone_gravity));
+                    }
+                }
+                GravityMode::Add(zone_gravity) => added += zone_gravity,
+            }
+        }
+    }
+    let mut pull = override_gravity.map_or(config.gravity, |(_, _, zone_gravity)| zone_gravity) + added;
+    if let Some(direction) = gravity.direction {
+        pull = direction.normalize_or_zero() * pull.length();
+    }
+    pull * gravity.scale
+}
+
+pub fn apply_gravity(
+    config: Res<PhysicsConfig>,
+    zone_query: GravityZones,
+    mut query: Query<(&mut Acceleration, &Gravity, Option<&GlobalPosition>), Without<Sleeping>>
+) {
     eprintln!("apply_gravity");
-    for mut accel in query.iter_mut() {
-        accel.0 += GRAVITY_VECTOR;
+    for (mut accel, gravity, pos) in query.iter_mut() {
+        accel.0 += effective_gravity(&config, &zone_query, gravity, pos);
+    }
+}
+
+// how much of the shape is in each fluid, we only go by the bounding boxes so slopes and
+// circles are a little off but fluids are almost always rects anyway
+pub fn detect_submersion(
+    fluid_query: Query<(Entity, &GlobalPosition, &GlobalRotation, &Shape), With<FluidVolume>>,
+    mut query: Query<(&mut Submerged, &GlobalPosition, &GlobalRotation, &Shape), Without<Sleeping>>
+) {
+    for (mut submerged, pos, rot, shape) in query.iter_mut() {
+        submerged.fraction = 0.0;
+        submerged.fluid = None;
+        let aabb = Aabb::from_shape(&pos.0, shape, rot.0);
+        let area = (aabb.max - aabb.min).x * (aabb.max - aabb.min).y;
+        if area <= 0.0 {
+            continue;
+        }
+        for (fluid, fluid_pos, fluid_rot, fluid_shape) in fluid_query.iter() {
+            let fluid_aabb = Aabb::from_shape(&fluid_pos.0, fluid_shape, fluid_rot.0);
+            let overlap = (aabb.max.min(fluid_aabb.max) - aabb.min.max(fluid_aabb.min)).max(Vec2::ZERO);
+            // if we're in more than one we go with the one we're deepest in
+            let fraction = (overlap.x * overlap.y / area).min(1.0);
+            if fraction > submerged.fraction {
+                submerged.fraction = fraction;
+                submerged.fluid = Some(fluid);
+            }
+        }
+    }
+}
+
+type FloatingBodies<'w, 's> = Query<'w, 's,
+    (&'static mut Acceleration, &'static Submerged, &'static Shape, &'static Gravity, Option<&'static GlobalPosition>, Option<&'static Mass>),
+    Without<Sleeping>>;
+
+// pushes against the gravity the body feels with the weight of the fluid we're pushing out of the way,
+// bodies without gravity have nothing to float against
+pub fn apply_buoyancy(
+    config: Res<PhysicsConfig>,
+    zone_query: GravityZones,
+    fluid_query: Query<&FluidVolume>,
+    mut query: FloatingBodies
+) {
+    for (mut accel, submerged, shape, gravity, pos, mass) in query.iter_mut() {
+        let Some(fluid) = submerged.fluid.and_then(|fluid| fluid_query.get(fluid).ok()) else {
+            continue;
+        };
+        let mass = mass.map_or(1.0, |mass| mass.0);
+        if mass <= 0.0 {
+            continue;
+        }
+        let pull = effective_gravity(&config, &zone_query, gravity, pos);
+        accel.0 -= pull * fluid.density * shape_area(shape) * submerged.fraction / mass;
+    }
+}
+
+fn shape_area(shape: &Shape) -> f32 {
+    match shape {
+        Shape::Rect(size) => size.x * size.y,
+        Shape::Circle(radius) => std::f32::consts::PI * radius * radius,
+        Shape::Poly(points) => {
+            // shoelace formula
+            let twice_area: f32 = points.iter().zip(points.iter().cycle().skip(1))
+                .map(|(point, next)| point.perp_dot(*next))
+                .sum();
+            twice_area.abs() * 0.5
+        }
     }
 }
 
 // we are tyring to fix the drifting left bug
-pub fn apply_resistance(mut query: Query<(&mut Acceleration, &Velocity, &Resistance)>) {
+// bodies in a fluid get the fluids drag on top of their own resistance
+pub fn apply_resistance(
+    config: Res<PhysicsConfig>,
+    fluid_query: Query<&FluidVolume>,
+    mut query: Query<(&mut Acceleration, &Velocity, &Resistance, Option<&Submerged>), Without<Sleeping>>
+) {
     eprintln!("apply_resistance");
-    for (mut accel, vel, resist) in query.iter_mut() {
-        if vel.0.x.abs() > MU {
-            accel.0.x -= vel.0.x * vel.0.x * resist.0.x * vel.0.signum().x;
+    for (mut accel, vel, resist, submerged) in query.iter_mut() {
+        let drag = submerged
+            .and_then(|submerged| submerged.fluid.and_then(|fluid| fluid_query.get(fluid).ok()).map(|fluid| fluid.drag * submerged.fraction))
+            .unwrap_or(0.0);
+        let resist = resist.0 * (1.0 + drag);
+        if vel.0.x.abs() > config.epsilon {
+            accel.0.x -= vel.0.x * vel.0.x * resist.x * vel.0.signum().x;
         }
-        if vel.0.y.abs() > MU {
-            accel.0.y -= vel.0.y * vel.0.y * resist.0.y * vel.0.signum().y;
+        if vel.0.y.abs() > config.epsilon {
+            accel.0.y -= vel.0.y * vel.0.y * resist.y * vel.0.signum().y;
         }
     }
 }
 
 // TODO fix the drifitn gleft bug
-pub fn apply_friction(mut query: Query<(&mut Acceleration, &Velocity, &Friction)>) {
+// whatever we're standing on scales our friction, ice makes us slide and mud slows us down
+// friction slows us down to the speed of the ground so conveyor belts drag us along
+pub fn apply_friction(
+    config: Res<PhysicsConfig>,
+    mut query: Query<(&mut Acceleration, &Velocity, &Friction, Option<&WallSensor>), Without<Sleeping>>
+) {
     eprintln!("apply_friction");
-    for (mut accel, vel, friction) in query.iter_mut() {
-        if vel.0.x.abs() > MU {
-            accel.0.x -= vel.0.signum().x * friction.0.x;
+    for (mut accel, vel, friction, sensor) in query.iter_mut() {
+        let surface = sensor.map_or(1.0, |sensor| sensor.ground_material.friction);
+        let surface_vel = sensor.map_or(Vec2::ZERO, |sensor| sensor.ground_velocity);
+        let friction = friction.0 * surface;
+        let vel = vel.0 - surface_vel;
+        // friction can stop us but never push us back the other way
+        if vel.x.abs() > config.epsilon {
+            accel.0.x -= vel.signum().x * friction.x.min(vel.x.abs() / config.time_step);
         }
-        if vel.0.y.abs() > MU {
-            accel.0.y -= vel.0.signum().y * friction.0.y;
+        if vel.y.abs() > config.epsilon {
+            accel.0.y -= vel.signum().y * friction.y.min(vel.y.abs() / config.time_step);
         }
     }
 }
 
-pub fn apply_accel(mut query: Query<(&mut Acceleration, &mut Velocity)>){
+pub fn apply_accel(config: Res<PhysicsConfig>, mut query: Query<(&mut Acceleration, &mut Velocity), Without<Sleeping>>){
     eprintln!("apply_accel");
     for (mut accel, mut vel) in query.iter_mut() {
-        vel.0 += accel.0*PHYSICS_TIME_STEP;
+        vel.0 += accel.0*config.time_step;
         accel.0 = Vec2::ZERO;
     } 
 }
 
-pub fn apply_velocity(mut query: Query<(&Velocity, &mut Position)>) {
+// wall colliders get moved by handle_wall_collisions as they slide along the walls
+type MovingBodies<'w, 's> = Query<'w, 's, (&'static Velocity, &'static mut Position), (Without<Sleeping>, Without<WallCollider>)>;
+
+pub fn apply_velocity(config: Res<PhysicsConfig>, mut query: MovingBodies) {
     eprintln!("apply_velocity");
     for (vel, mut pos) in query.iter_mut() {
-        pos.0 += vel.0*PHYSICS_TIME_STEP;
+        pos.0 += vel.0*config.time_step;
     }
 }
 
-pub fn apply_angular_velocity(mut query: Query<(&AngularVelocity, &mut Rotation)>) {
+pub fn apply_angular_velocity(config: Res<PhysicsConfig>, mut query: Query<(&AngularVelocity, &mut Rotation), Without<Sleeping>>) {
     eprintln!("apply_angular_velocity");
     for (vel, mut rot) in query.iter_mut() {
-        rot.0 += vel.0*PHYSICS_TIME_STEP;
+        rot.0 += vel.0*config.time_step;
+    }
+}
+
+pub fn store_previous_state(mut query: Query<(&Position, &Rotation, &mut PreviousPosition, &mut PreviousRotation)>) {
+    for (pos, rot, mut prev_pos, mut prev_rot) in query.iter_mut() {
+        prev_pos.0 = pos.0;
+        prev_rot.0 = rot.0;
     }
 }
 
-pub fn apply_position_to_transform(mut query: Query<(&Position, &mut Transform)>) {
+// the global pose is still from the end of the last tick here, nothing has moved yet
+// something spawned since the last tick hasn't had its global pose worked out, it's still
+// sat at the origin, so it gets no pose and isn't swept on its first tick
+pub fn store_ccd_poses(mut query: Query<(Ref<GlobalPosition>, &GlobalRotation, &mut Ccd)>) {
+    for (pos, rot, mut ccd) in query.iter_mut() {
+        ccd.last_pose = if pos.is_added() { None } else { Some((pos.0, rot.0)) };
+    }
+}
+
+// interpolated bodies get their transform set every frame by interpolate_transforms instead
+pub fn apply_position_to_transform(mut query: Query<(&Position, &mut Transform), Without<Interpolation>>) {
     eprintln!("apply_position_to_transform");
     for (pos, mut trans) in query.iter_mut() {
         trans.translation = pos.0.extend(0.0);
     }
 }
 
-pub fn apply_rotation_to_transform(mut query: Query<(&Rotation, &mut Transform)>) {
+pub fn apply_rotation_to_transform(mut query: Query<(&Rotation, &mut Transform), Without<Interpolation>>) {
     eprintln!("apply_rotation_to_transform");
     for (rot, mut trans) in query.iter_mut() {
         trans.rotation = Quat::from_rotation_z(rot.0);
     }
 }
 
-
-//pos1 and pos2 are teh upper left corner of the rect
-pub fn rectangles_casted_collision(
-    pos1: &Vec2, size1: &Vec2, vel1: &Vec2, 
-    pos2: &Vec2, size2: &Vec2, vel2: &Vec2
-) -> Option<f32> {
-    let cast1 = Vec2 { x:pos1.x + vel1.x * PHYSICS_TIME_STEP, y:pos1.y + vel1.y * PHYSICS_TIME_STEP };
-    let cast2 = Vec2 { x:pos2.x + vel2.x * PHYSICS_TIME_STEP, y:pos2.y + vel2.y * PHYSICS_TIME_STEP };
-    if aabb_collision(&cast1, size1, &cast2, size2) {
-        let horizontal = if pos1.x >= pos2.x + size2.x {
-            Some((std::f32::consts::PI, (pos2.x + size2.x - pos1.x) / (vel1.x - vel2.x)))            
-        } else if pos1.x + size1.x <= pos2.x {
-            Some((0.0, (pos2.x - (pos1.x + size1.x)) / (vel1.x - vel2.x)))
-        } else {
-            None
-        };
-
-        let vertical = if pos1.y <= pos2.y - size2.y {
-            Some((std::f32::consts::PI/2.0, (pos2.y - size2.y - pos1.y) / (vel1.y - vel2.y)))
-        } else if pos1.y - size1.y >= pos2.y {
-            Some((std::f32::consts::PI * 3.0 / 2.0, (pos2.y - (pos1.y - size1.y)) / (vel1.x - vel2.x)))
-        } else {
-            None
-        };
-
-        match (horizontal, vertical) {
-            (Some((h_ang, tx)), Some((v_ang, ty))) => {
-                if tx < ty {
-                    Some(h_ang)
-                } else {
-                    Some(v_ang)
+// update our 2d tranforms starting from the top of each transform tree, like the GameWorld,
+// anything with a transform that isn't under another one is already in world space
+pub fn propagate_transform(
+    root_query: Query<(Entity, Option<&Parent>), With<TwoDimTrans>>, 
+    child_query: Query<&Children, With<TwoDimTrans>>,
+    mut transform_query: Query<(&mut TwoDimTrans, &mut GlobalRotation, &mut GlobalPosition, &Position, &Rotation)>
+) {
+    // stack to use for DFS down the transform tree
+    let mut stack = Vec::new();
+    for (root, parent) in root_query.iter() {
+        if parent.is_some_and(|parent| root_query.contains(parent.get())) {
+            continue;
+        }
+        if let Ok((mut trans, mut rot, mut pos, local_pos, local_rot)) = transform_query.get_mut(root) {
+            trans.0 = Mat3::from_scale_angle_translation(Vec2::ONE, local_rot.0, local_pos.0);
+            pos.0 = local_pos.0;
+            rot.0 = local_rot.0;
+        }
+        stack.push(root);
+    }
+    while stack.len() > 0 {
+
+        // get the top element of the stack extract the value we need from it
+        let cur = stack.pop().unwrap();
+        if let (Ok(children), Ok((trans, rot, _, _, _))) = (child_query.get(cur), transform_query.get(cur)) {
+            let trans = trans.clone();
+            let rot = rot.0;
+
+            for child in children.iter() {
+                // get the child transform values
+                if let Ok((mut child_trans, mut child_rot, 
+                    mut child_pos, local_pos, local_rot)) 
+                    = transform_query.get_mut(*child) {
+                    // generate the local transform
+                    child_trans.0 = Mat3::from_scale_angle_translation(Vec2::ONE, local_rot.0, local_pos.0);
+                    // multiply the teh parent tranfrom
+                    child_trans.0 = trans.0*child_trans.0;
+                    // tranform the local point by the child point
+                    child_pos.0 = trans.0.transform_point2(local_pos.0);
+                    // transform the local rotation
+                    child_rot.0 = rot+local_rot.0;
+                    // add the child to the stack
+                    stack.push(*child);
+                    
                 }
             }
-            (None, Some((v_ang, ty))) => Some(v_ang),
-            (Some((h_ang, tx)), None) => Some(h_ang),
-            (None, None) => panic!("we collided but can't find the angel") 
         }
-        
-    } else {
-        None
     }
 }
 
-fn detect_collision_pair(
-    pos1: &Vec2, shape1: &Shape, angle1: f32,
-    pos2: &Vec2, shape2: &Shape, angle2: f32) -> bool 
-{
-    match (shape1, shape2) {
-        (Shape::Rect(size1), Shape::Rect(size2)) => {
-            if angle1.abs() < MU && angle2.abs() < MU {
-                print!("angle1: {}, angle2: {} ", angle1, angle2);
-                aabb_collision(pos1, size1, pos2, size2)
-            } else {
-                let points1 = generate_rectangle_points(pos1, size1, angle1);
-                let points2 = generate_rectangle_points(pos2, size2, angle2);
-                //print!("{:?}, {:?} ", points1, points2);
-                sat_collision(points1, points2)
-            }
-        }
-        (Shape::Circle(radius1), Shape::Circle(radius2)) => {
-            circles_collision(pos1, radius1, pos2, radius2)
-        }
-        (Shape::Circle(radius), Shape::Rect(size)) => {
-            if angle2.abs() < MU {
-                aabb_circle_collision(pos2, size, pos1, *radius)
-            } else {
-                let points = generate_rectangle_points(pos2, size, angle2);
-                sat_circle_collision(points, pos1, *radius)
-            }
-        }
-        (Shape::Rect(size), Shape::Circle(radius)) => {
-            if angle1.abs() < MU {
-                aabb_circle_collision(pos1, size, pos2, *radius)
-            } else {
-                let points = generate_rectangle_points(pos1, size, angle1);
-                sat_circle_collision(points, pos2, *radius)
-            }
-        }
-        (_,_) => {
-            panic!("Unhandled collision");
-        }
-    }
+#[derive(Component, Default)]
+pub struct Wall;
+
+#[derive(Component, Default)]
+pub struct WallCollider;
+
+#[derive(Component, Default, Clone)]
+pub struct WallSensor {
+    pub left: bool,
+    pub right: bool,
+    pub down: bool,
+    // the wall we are standing on and the normal pointing down into it
+    pub ground: Option<Entity>,
+    pub ground_normal: Vec2,
+    // what it's made of, plain when we aren't on anything
+    pub ground_material: SurfaceMaterial,
+    // how fast the top of it is moving if it's a conveyor belt
+    pub ground_velocity: Vec2,
 }
 
-// TODO Shoudl I inline these fucntions
-// Check for a collision between two rectangles
-fn circles_collision(pos1: &Vec2, radius1: &f32, pos2: &Vec2, radius2: &f32) -> bool {
-    pos1.distance(*pos2) < radius1 + radius2
+// how much a hit normal has to point along an axis to count for the sensors, about 45 degrees
+const SENSOR_NORMAL_THRESHOLD: f32 = 0.7;
+// anything hitting slower than this just stops instead of bouncing, so things can come to rest on a bounce pad
+const SURFACE_BOUNCE_SPEED: f32 = 1.0;
+
+// what a wall feels like, walls without one act like SurfaceMaterial::default
+// friction multiplies the Friction of whatever is standing on it, restitution bounces things off it
+// (the bouncier of this and the bodies Restitution wins) and stickiness from 0 to 1 is how much of the
+// speed along the wall gets taken away over a second of pushing into it
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub struct SurfaceMaterial {
+    pub friction: f32,
+    pub restitution: f32,
+    pub stickiness: f32,
 }
 
-// Check for a collision between axis aligned bounding boxes (two rectangles with no rotation)
-// takes in the upper left corner of the rect
-fn aabb_collision(pos1: &Vec2, size1: &Vec2, pos2: &Vec2, size2: &Vec2) -> bool {
-    pos1.x <= pos2.x + size2.x && 
-        pos1.x + size1.x >= pos2.x &&
-        pos1.y >= pos2.y - size2.y && 
-        pos1.y - size1.y <= pos2.y
+impl SurfaceMaterial {
+    pub const ICE: SurfaceMaterial = SurfaceMaterial { friction: 0.1, restitution: 0.0, stickiness: 0.0 };
+    pub const MUD: SurfaceMaterial = SurfaceMaterial { friction: 3.0, restitution: 0.0, stickiness: 0.9 };
+    pub const BOUNCE_PAD: SurfaceMaterial = SurfaceMaterial { friction: 1.0, restitution: 0.9, stickiness: 0.0 };
+    pub const STICKY: SurfaceMaterial = SurfaceMaterial { friction: 1.0, restitution: 0.0, stickiness: 0.99 };
 }
 
-// Check for a collision between axis aligned bounding box and a circle
-fn aabb_circle_collision(rect_pos: &Vec2, size: &Vec2, circ_pos: &Vec2, radius: f32) -> bool {
-    let x_loc = if rect_pos.x > circ_pos.x {
-        Some(rect_pos.x)
-    } else if rect_pos.x + size.x < circ_pos.x {
-        Some(rect_pos.x + size.x)
-    } else {
-        None
-    };
-    let y_loc = if rect_pos.y < circ_pos.y {
-        Some(rect_pos.y)
-    } else if rect_pos.y - size.y > circ_pos.y {
-        Some(rect_pos.y - size.y)
-    } else {
-        None
-    };
-    match (x_loc, y_loc) {
-        (Some(x), Some(y)) => Vec2::new(x, y).distance(*circ_pos) < radius,
-        (Some(x), None) => (x - circ_pos.x).abs() < radius,
-        (None, Some(y)) => (y - circ_pos.y).abs() < radius,
-        (None, None) => true,
+// makes the surface of a wall move without the wall going anywhere, for conveyor belts and escalators
+// positive goes clockwise around the wall, so right along the top and down the right side
+// things standing on it get dragged along by their friction and things pushing into the sides get carried
+#[derive(Component, Default, Debug, Clone, Copy)]
+pub struct SurfaceVelocity(pub f32);
+
+impl SurfaceVelocity {
+    // how fast the surface is moving where the normal (pointing into the wall) hits it
+    pub fn along(&self, normal: &Vec2) -> Vec2 {
+        normal.perp() * self.0
     }
 }
 
-fn get_axes(points: &Vec<Vec2>) -> Vec<Vec2> {
-    let mut norms: Vec<Vec2> = Vec::new();
-    for i in 0..points.len() {
-        norms.push(points[i] - points[(i+1) % points.len()]);
+impl Default for SurfaceMaterial {
+    fn default() -> Self {
+        SurfaceMaterial { friction: 1.0, restitution: 0.0, stickiness: 0.0 }
     }
-    norms.iter_mut().map(|x| x.perp().normalize()).collect()
 }
 
-fn project_shape(points: &Vec<Vec2>, axis: &Vec2) -> (f32,f32) {
-    let mut min: f32 = axis.dot(points[0]);
-    let mut max: f32 = min;
-    for i in 1..points.len() {
-        let val = axis.dot(points[i]);
-        if val < min {
-            min = val;
-        } else if val > max {
-            max = val;
-        }
-    }
-    return (min, max)
+// lets a wall collider walk up and down slopes
+// anything steeper than max_walkable_angle (radians off flat) can't be stood on and we slide down it
+// when we walk off the top of a slope we snap back down to the ground if its within snap_distance
+#[derive(Component)]
+pub struct SlopeWalker {
+    pub max_walkable_angle: f32,
+    pub snap_distance: f32,
 }
 
-// TODO
-fn sat_collision(points1: Vec<Vec2>, points2: Vec<Vec2>) -> bool {
-    let axes = get_axes(&points1);
-    for axis in axes {
-        let range1 = project_shape(&points1, &axis);
-        let range2 = project_shape(&points2, &axis);
-        if range1.1 < range2.0 && range1.0 > range2.0 {
-            return false;
-        }
-    }
-    let axes = get_axes(&points2);
-    for axis in axes {
-        let range1 = project_shape(&points1, &axis);
-        let range2 = project_shape(&points2, &axis);
-        if range1.1 < range2.0 && range1.0 > range2.0 {
-            return false;
+pub const DEFAULT_MAX_WALKABLE_ANGLE: f32 = 50.0 * std::f32::consts::PI / 180.0;
+pub const DEFAULT_SNAP_DISTANCE: f32 = 0.3;
+
+impl Default for SlopeWalker {
+    fn default() -> Self {
+        SlopeWalker { 
+            max_walkable_angle: DEFAULT_MAX_WALKABLE_ANGLE, 
+            snap_distance: DEFAULT_SNAP_DISTANCE 
         }
     }
-
-    true
 }
 
+// a wall you can jump up through and stand on, it only stops things landing on it from above
+#[derive(Component, Default)]
+pub struct OneWayPlatform;
+
+// while this is on a wall collider it falls through one way platforms
+#[derive(Component, Clone)]
+pub struct DropThrough(pub Timer);
 
+pub const DROP_THROUGH_TIME: f32 = 0.2;
 
-// TODO
-fn sat_circle_collision(points: Vec<Vec2>, circ_pos: &Vec2, radius: f32) -> bool {
-    false
+impl Default for DropThrough {
+    fn default() -> Self {
+        DropThrough(Timer::from_seconds(DROP_THROUGH_TIME, TimerMode::Once))
+    }
 }
 
-fn generate_rectangle_points(pos: &Vec2, size: &Vec2, angle: f32) -> Vec<Vec2> {
-    let hori_offset = (size.x / 2.0) * Vec2::from_angle(angle);
-    let vert_offset = (size.y / 2.0) * Vec2::from_angle(angle).perp();
-    vec![*pos - hori_offset + vert_offset,
-        *pos + hori_offset + vert_offset,
-        *pos + hori_offset - vert_offset,
-        *pos - hori_offset - vert_offset] 
+// how many walls we can slide off of in one tick, a corner takes two and a corner
+// made of a few walls meeting at the seams can take more
+pub const MAX_WALL_PASSES: usize = 4;
+
+type WallQuery<'w, 's> = Query<'w, 's,
+    (Entity, &'static Position, &'static Shape, Option<&'static Rotation>, Option<&'static Velocity>, Option<&'static OneWayPlatform>),
+    (With<Wall>, Without<WallCollider>)>;
+type WallColliderQuery<'w, 's> = Query<'w, 's,
+    (&'static mut Position, &'static Shape, Option<&'static Rotation>, &'static mut Velocity, Option<&'static mut WallSensor>,
+        Option<&'static DropThrough>, Option<&'static SlopeWalker>, Option<&'static Restitution>),
+    (With<WallCollider>, Without<Wall>, Without<Sleeping>)>;
+
+type SurfaceQuery<'w, 's> = Query<'w, 's, (Option<&'static SurfaceMaterial>, Option<&'static SurfaceVelocity>)>;
+
+// one sweep handle_wall_collisions did this tick, from position along motion until it hit something
+// at time (0 to 1 of motion) or all the way if it didn't
+pub struct WallCast {
+    pub position: Vec2,
+    pub shape: Shape,
+    pub angle: f32,
+    pub motion: Vec2,
+    pub time: Option<f32>,
 }
 
+// every sweep from the last tick, only kept while something (the debug overlay) puts this resource in
+#[derive(Resource, Default)]
+pub struct WallCasts(pub Vec<WallCast>);
+
+pub fn handle_wall_collisions(
+    config: Res<PhysicsConfig>,
+    wall_query: WallQuery,
+    surface_query: SurfaceQuery,
+    mut wall_collider_query: WallColliderQuery,
+    mut casts: Option<ResMut<WallCasts>>
+) {
+    eprintln!("cast");
+    if let Some(casts) = casts.as_mut() {
+        casts.0.clear();
+    }
+    let zero_velocity = Velocity(Vec2::ZERO);
+    for (mut col_pos, col_shape, col_rot, mut col_vel, mut wall_sensor, dropping, walker, restitution) in wall_collider_query.iter_mut() {
+        // how far up the normal of the ground has to point for us to stand on it
+        let walkable = walker.map_or(SENSOR_NORMAL_THRESHOLD, |walker| walker.max_walkable_angle.cos());
+        let was_down = wall_sensor.as_ref().is_some_and(|sensor| sensor.down);
+        if let Some(sensor) = wall_sensor.as_mut() {
+            sensor.left = false;
+            sensor.right = false;
+            sensor.down = false;
+            sensor.ground = None;
+            sensor.ground_normal = Vec2::ZERO;
+            sensor.ground_material = SurfaceMaterial::default();
+            sensor.ground_velocity = Vec2::ZERO;
+        }
+        let col_angle = col_rot.map_or(0.0, |rot| rot.0);
+        // each pass we find the first wall we would hit over what's left of the tick, move up to it,
+        // take out the velocity going into it and try again with the rest, so it doesn't matter what order
+        // the walls are in and we slide over the seams between walls instead of catching on them
+        // this moves us for the whole tick so apply_velocity leaves wall colliders alone
+        let mut elapsed = 0.0;
+        let mut remaining = 1.0;
+        for pass in 0..MAX_WALL_PASSES {
+            let step = config.time_step * remaining;
+            let mut first: Option<(Entity, CastHit, Vec2)> = None;
+            for (wall, wall_pos, wall_shape, wall_rot, wall_vel, one_way) in wall_query.iter() {
+                let wall_angle = wall_rot.map_or(0.0, |rot| rot.0);
+                let wall_vel = wall_vel.unwrap_or(&zero_velocity);
+                // the walls haven't moved yet this tick, so catch them up to where they are by now
+                let wall_pos = &Position(wall_pos.0 + wall_vel.0 * config.time_step * elapsed);
+                let one_way = one_way.is_some();
+                if one_way {
+                    // one way platforms only count if we start all the way above them and are falling onto them
+                    let col_aabb = Aabb::from_shape(&col_pos.0, col_shape, col_angle);
+                    let wall_aabb = Aabb::from_shape(&wall_pos.0, wall_shape, wall_angle);
+                    if dropping.is_some() || col_aabb.min.y < wall_aabb.max.y - config.epsilon || col_vel.0.y > wall_vel.0.y {
+                        continue;
+                    }
+                }
+                let hit = match (&col_shape, &wall_shape) {
+                    (Shape::Rect(col_size), Shape::Rect(wall_size)) if col_angle.abs() < config.epsilon && wall_angle.abs() < config.epsilon => {
+                        // Calculat the upper left position for easier fucntion calcs
+                        let col_upper_left = Vec2 {x: col_pos.0.x - col_size.x*0.5, y: col_pos.0.y + col_size.y*0.5};
+                        let wall_upper_left = Vec2 {x: wall_pos.0.x - wall_size.x*0.5, y: wall_pos.0.y + wall_size.y*0.5};
+                        rectangles_casted_collision(
+                            &col_upper_left, col_size, &col_vel.0, 
+                            &wall_upper_left, wall_size, &wall_vel.0,
+                            step)
+                    }
+                    (_, _) => {
+                        shapes_casted_collision(
+                            &col_pos.0, col_shape, col_angle,
+                            &wall_pos.0, wall_shape, wall_angle,
+                            &((col_vel.0 - wall_vel.0) * step))
+                    }
+                };
+                // the normal of the hit points from the collider into the wall, one way platforms only stop us from above
+                let Some(hit) = hit.filter(|hit| !one_way || hit.normal.y < -SENSOR_NORMAL_THRESHOLD) else {
+                    continue;
+                };
+                // ties go to the older wall so it doesn't come down to query order
+                if first.as_ref().is_none_or(|(first_wall, first, _)| (hit.time, wall) < (first.time, *first_wall)) {
+                    first = Some((wall, hit, wall_vel.0));
+                }
+            }
+
+            if let Some(casts) = casts.as_mut() {
+                casts.0.push(WallCast {
+                    position: col_pos.0,
+                    shape: col_shape.clone(),
+                    angle: col_angle,
+                    motion: col_vel.0 * step,
+                    time: first.as_ref().map(|(_, hit, _)| hit.time),
+                });
+            }
 
-// lets check if they are on screen for now
-// so we can ignore things that aren't
-fn in_active_range(bodies: &Vec<(&Position, &Shape)>) {
+            let Some((wall, hit, wall_vel)) = first else {
+                break;
+            };
+
+            if hit.time <= 0.0 {
+                // we started inside the wall, push back out the shortest way instead of moving
+                let (_, wall_pos, wall_shape, wall_rot, _, _) = wall_query.get(wall).unwrap();
+                let depth = detect_collision_pair(
+                    &col_pos.0, col_shape, col_angle,
+                    &(wall_pos.0 + wall_vel * config.time_step * elapsed), wall_shape, wall_rot.map_or(0.0, |rot| rot.0))
+                    .map_or(0.0, |contact| contact.depth);
+                col_pos.0 -= hit.normal * (depth + config.skin_width);
+            } else {
+                // move up to the wall and back off a hair so we aren't inside it
+                col_pos.0 += col_vel.0 * step * hit.time - hit.normal * config.skin_width;
+                elapsed += remaining * hit.time;
+                remaining *= 1.0 - hit.time;
+            }
 
-}
+            let normal = hit.normal;
+            let grounded = -normal.y >= walkable;
+            let (surface, surface_vel) = surface_of(&surface_query, wall);
+            if let Some(sensor) = wall_sensor.as_mut() {
+                if grounded {
+                    sensor.down = true;
+                    sensor.ground = Some(wall);
+                    sensor.ground_normal = normal;
+                    sensor.ground_material = surface;
+                    sensor.ground_velocity = surface_vel.map_or(Vec2::ZERO, |surface_vel| surface_vel.along(&normal));
+                } else if normal.x < -SENSOR_NORMAL_THRESHOLD {
+                    sensor.left = true;
+                } else if normal.x > SENSOR_NORMAL_THRESHOLD {
+                    sensor.right = true;
+                }
+            }
+            // take out the part of the velocity going into the wall so we slide along it
+            // this is relative to the wall so we keep up with moving walls
+            let bounce = surface.restitution.max(restitution.map_or(0.0, |restitution| restitution.0));
+            let into_wall = (col_vel.0 - wall_vel).dot(normal);
+            if into_wall > 0.0 {
+                // we hit it every tick while pushing into it so take out this ticks share of the stickiness
+                let along_wall = (col_vel.0 - wall_vel) - normal * into_wall;
+                col_vel.0 -= along_wall * (1.0 - (1.0 - surface.stickiness.clamp(0.0, 1.0)).powf(config.time_step));
+                if bounce > 0.0 && into_wall > SURFACE_BOUNCE_SPEED {
+                    col_vel.0 -= normal * into_wall * (1.0 + bounce);
+                } else if walker.is_some() && grounded {
+                    // keep our speed across the ground and follow the slope up or down with it
+                    // instead of losing the part of it that went into the slope
+                    col_vel.0 = wall_vel + follow_ground(&(col_vel.0 - wall_vel), &normal);
+                } else {
+                    col_vel.0 -= normal * into_wall;
+                }
+                // the sides of a conveyor grab us and move us along with them, the ground does it
+                // through friction in apply_friction instead so ice conveyors are still slippery
+                if let (Some(surface_vel), false) = (surface_vel, grounded) {
+                    let along_wall = (col_vel.0 - wall_vel) - normal * (col_vel.0 - wall_vel).dot(normal);
+                    col_vel.0 += surface_vel.along(&normal) - along_wall;
+                }
+            }
 
-// the concept here is to decte paris to check
-fn broad_phase() {
-    
+            // still hitting things after all the passes, we're wedged in a corner so stop with the wall
+            if pass == MAX_WALL_PASSES - 1 {
+                col_vel.0 = wall_vel;
+            }
+        }
+        // nothing left in the way, move the rest of the tick
+        col_pos.0 += col_vel.0 * config.time_step * remaining;
+
+        // we were on the ground last tick and just walked off it, if we are going down a slope
+        // (or off a small step) stick to the ground instead of flying off it
+        if let (Some(walker), Some(sensor)) = (walker, wall_sensor.as_mut()) {
+            if was_down && !sensor.down && col_vel.0.y <= 0.0 {
+                let drop = Vec2 { x: 0.0, y: -walker.snap_distance };
+                let mut nearest: Option<(Entity, CastHit)> = None;
+                for (wall, wall_pos, wall_shape, wall_rot, wall_vel, one_way) in wall_query.iter() {
+                    if one_way.is_some() && dropping.is_some() {
+                        continue;
+                    }
+                    // we've moved the whole tick so check against where the wall ends up too
+                    let wall_pos = wall_pos.0 + wall_vel.unwrap_or(&zero_velocity).0 * config.time_step;
+                    let hit = shapes_casted_collision(
+                        &col_pos.0, col_shape, col_angle,
+                        &wall_pos, wall_shape, wall_rot.map_or(0.0, |rot| rot.0),
+                        &drop);
+                    if let Some(hit) = hit {
+                        if -hit.normal.y >= walkable && nearest.is_none_or(|(_, nearest)| hit.time < nearest.time) {
+                            nearest = Some((wall, hit));
+                        }
+                    }
+                }
+                if let Some((wall, hit)) = nearest {
+                    col_pos.0 += drop * hit.time - hit.normal * config.skin_width;
+                    col_vel.0 = follow_ground(&col_vel.0, &hit.normal);
+                    sensor.down = true;
+                    sensor.ground = Some(wall);
+                    sensor.ground_normal = hit.normal;
+                    let (surface, surface_vel) = surface_of(&surface_query, wall);
+                    sensor.ground_material = surface;
+                    sensor.ground_velocity = surface_vel.map_or(Vec2::ZERO, |surface_vel| surface_vel.along(&hit.normal));
+                }
+            }
+        }
+    } 
 }
 
-// here is where we check the pairs
-// how shoudl we do this passing pairs to this? events?
-// directly passing the pairs as a vector of tuples somehow?
+// walls without a SurfaceMaterial are plain and without a SurfaceVelocity stay still
+fn surface_of(surface_query: &SurfaceQuery, wall: Entity) -> (SurfaceMaterial, Option<SurfaceVelocity>) {
+    surface_query.get(wall).map_or((SurfaceMaterial::default(), None), |(surface, surface_vel)|
+        (surface.copied().unwrap_or_default(), surface_vel.copied()))
+}
 
-//how do we act on the pairs, a big match statment? somehow calling a function of each entity and matchign there?
-/* 
-match
-    player, enemy
-    enemy, player
+// turn a velocity into one along the ground with the same horizontal speed
+// normal points down into the ground
+fn follow_ground(vel: &Vec2, normal: &Vec2) -> Vec2 {
+    let tangent = normal.perp();
+    if tangent.x.abs() < MU {
+        return Vec2::ZERO;
+    }
+    tangent * (vel.x / tangent.x)
+}
 
-or
+type InterpolatedBodies<'w, 's> = Query<'w, 's,
+    (&'static Interpolation, &'static Position, &'static Rotation, Ref<'static, PreviousPosition>, &'static PreviousRotation, &'static mut Transform)>;
+
+// runs every frame, how far we are into the next tick decides how far to blend
+pub fn interpolate_transforms(
+    fixed_time: Res<FixedTime>,
+    mut query: InterpolatedBodies
+) {
+    let alpha = fixed_time.accumulated().as_secs_f32() / fixed_time.period.as_secs_f32();
+    for (interpolation, pos, rot, prev_pos, prev_rot, mut trans) in query.iter_mut() {
+        // nothing to blend from until the first tick after spawning
+        let mode = if prev_pos.is_added() { Interpolation::Off } else { *interpolation };
+        let (draw_pos, draw_rot) = match mode {
+            Interpolation::Off => (pos.0, rot.0),
+            Interpolation::Interpolate => (prev_pos.0.lerp(pos.0, alpha), prev_rot.0 + (rot.0 - prev_rot.0) * alpha),
+            Interpolation::Extrapolate => (pos.0 + (pos.0 - prev_pos.0) * alpha, rot.0 + (rot.0 - prev_rot.0) * alpha),
+        };
+        trans.translation = draw_pos.extend(trans.translation.z);
+        trans.rotation = Quat::from_rotation_z(draw_rot);
+    }
+}
 
-enemy.collider(player)
-player.collider(enemy)
 
-enemy
-    collider() {
-        match
-            player
-            hazard
+//pos1 and pos2 are teh upper left corner of the rect
+// time is the fraction of the time step where they first touch and the normal points from the first rect
+// into the second, if they already overlap we get a time of 0 and the normal to push out along
+pub fn rectangles_casted_collision(
+    pos1: &Vec2, size1: &Vec2, vel1: &Vec2, 
+    pos2: &Vec2, size2: &Vec2, vel2: &Vec2,
+    time_step: f32
+) -> Option<CastHit> {
+    if let Some(contact) = aabb_collision(pos1, size1, pos2, size2) {
+        return Some(CastHit { time: 0.0, normal: contact.normal });
+    }
+
+    let motion = (*vel1 - *vel2) * time_step;
+    let horizontal = axis_casted_overlap(pos1.x, pos1.x + size1.x, pos2.x, pos2.x + size2.x, motion.x)?;
+    let vertical = axis_casted_overlap(pos1.y - size1.y, pos1.y, pos2.y - size2.y, pos2.y, motion.y)?;
+    // they only touch once they overlap on both axes, so the later one is when they hit
+    let enter = horizontal.0.max(vertical.0);
+    let exit = horizontal.1.min(vertical.1);
+    if enter > exit || !(0.0..=1.0).contains(&enter) {
+        return None;
+    }
+    let normal = if horizontal.0 > vertical.0 {
+        Vec2::new(motion.x.signum(), 0.0)
+    } else {
+        Vec2::new(0.0, motion.y.signum())
+    };
+    Some(CastHit { time: enter, normal })
+}
+
+// when two ranges moving apart by motion start and stop overlapping, as fractions of the motion
+// none if they never will
+fn axis_casted_overlap(min1: f32, max1: f32, min2: f32, max2: f32, motion: f32) -> Option<(f32, f32)> {
+    if motion.abs() < MU {
+        if max1 < min2 || min1 > max2 {
+            return None;
+        }
+        return Some((f32::NEG_INFINITY, f32::INFINITY));
+    }
+    let (enter, exit) = if motion > 0.0 {
+        ((min2 - max1) / motion, (max2 - min1) / motion)
+    } else {
+        ((max2 - min1) / motion, (min2 - max1) / motion)
+    };
+    Some((enter, exit))
+}
+
+// when a shape moving through a step first touches another
+// time is the fraction of the cast (0 to 1) and the normal points from the first shape towards the second
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CastHit {
+    pub time: f32,
+    pub normal: Vec2,
+}
+
+// sweep the first shape by motion (how far it moves relative to the second shape)
+// and find when they first touch, pos1 and pos2 are the center of the shapes
+pub fn shapes_casted_collision(
+    pos1: &Vec2, shape1: &Shape, angle1: f32,
+    pos2: &Vec2, shape2: &Shape, angle2: f32,
+    motion: &Vec2,
+) -> Option<CastHit> {
+    if shape1.is_degenerate() || shape2.is_degenerate() {
+        return None;
+    }
+    // already touching, no need to cast
+    if let Some(contact) = detect_collision_pair(pos1, shape1, angle1, pos2, shape2, angle2) {
+        return Some(CastHit { time: 0.0, normal: contact.normal });
+    }
+
+    let motion = *motion;
+    match (shape1, shape2) {
+        (Shape::Circle(radius1), Shape::Circle(radius2)) => {
+            ray_circle_cast(pos1, &motion, pos2, radius1 + radius2)
+                .map(|(time, normal)| CastHit { time, normal: -normal })
+        }
+        (Shape::Circle(radius), _) => {
+            rounded_poly_cast(pos1, &motion, &shape_points(pos2, shape2, angle2), *radius)
+                .map(|(time, normal)| CastHit { time, normal: -normal })
+        }
+        (_, Shape::Circle(radius)) => {
+            // same as the circle moving backwards into us
+            rounded_poly_cast(pos2, &-motion, &shape_points(pos1, shape1, angle1), *radius)
+                .map(|(time, normal)| CastHit { time, normal })
+        }
+        (_, _) => {
+            sat_casted_collision(&shape_points(pos1, shape1, angle1), &shape_points(pos2, shape2, angle2), &motion)
+        }
+    }
+}
+
+// sweep a point from origin along dir into a shape centered at pos
+// returns the fraction of dir where we hit it and the normal of the shape where we hit
+pub fn ray_shape_cast(origin: &Vec2, dir: &Vec2, pos: &Vec2, shape: &Shape, angle: f32) -> Option<(f32, Vec2)> {
+    if shape.is_degenerate() {
+        return None;
+    }
+    match shape {
+        Shape::Circle(radius) => ray_circle_cast(origin, dir, pos, *radius),
+        _ => {
+            if point_in_shape(origin, pos, shape, angle) {
+                return Some((0.0, -dir.normalize_or_zero()));
+            }
+            rounded_poly_cast(origin, dir, &shape_points(pos, shape, angle), 0.0)
+        }
+    }
+}
+
+// is the point inside a shape centered at pos
+pub fn point_in_shape(point: &Vec2, pos: &Vec2, shape: &Shape, angle: f32) -> bool {
+    if shape.is_degenerate() {
+        return false;
+    }
+    match shape {
+        Shape::Circle(radius) => point.distance(*pos) <= *radius,
+        _ => {
+            // inside a convex shape means being on the same side of every edge
+            let points = shape_points(pos, shape, angle);
+            let mut side = 0.0;
+            for i in 0..points.len() {
+                let edge = points[(i + 1) % points.len()] - points[i];
+                let cross = edge.perp_dot(*point - points[i]);
+                if cross * side < 0.0 {
+                    return false;
+                }
+                if cross != 0.0 {
+                    side = cross;
+                }
+            }
+            true
+        }
+    }
+}
+
+// sweep a point from origin along dir and find the fraction of dir where it enters the circle
+// returns the normal of the circle where we hit it
+fn ray_circle_cast(origin: &Vec2, dir: &Vec2, center: &Vec2, radius: f32) -> Option<(f32, Vec2)> {
+    let offset = *origin - *center;
+    let a = dir.length_squared();
+    let b = offset.dot(*dir);
+    let c = offset.length_squared() - radius * radius;
+    if c <= 0.0 {
+        // started inside
+        return Some((0.0, offset.normalize_or_zero()));
+    }
+    if b >= 0.0 || a < MU {
+        return None;
+    }
+    let discriminant = b * b - a * c;
+    if discriminant < 0.0 {
+        return None;
+    }
+    let time = (-b - discriminant.sqrt()) / a;
+    if time > 1.0 {
+        return None;
+    }
+    Some((time, (*origin + *dir * time - *center) / radius))
+}
+
+// sweep a point from origin along dir into the polygon grown by radius (so its corners are rounded)
+// this is the same as sweeping a circle of that radius into the polygon
+// returns the fraction of dir where we hit and the normal of the polygon where we hit it
+fn rounded_poly_cast(origin: &Vec2, dir: &Vec2, points: &[Vec2], radius: f32) -> Option<(f32, Vec2)> {
+    let center = centroid(points);
+    let mut best: Option<(f32, Vec2)> = None;
+    for i in 0..points.len() {
+        let start = points[i];
+        let end = points[(i + 1) % points.len()];
+        let edge = end - start;
+        let mut normal = edge.perp().normalize_or_zero();
+        if normal == Vec2::ZERO {
+            continue;
+        }
+        if normal.dot(start - center) < 0.0 {
+            normal = -normal;
+        }
+
+        // the edge pushed out by the radius
+        let speed = normal.dot(*dir);
+        if speed < 0.0 {
+            let time = normal.dot(start + normal * radius - *origin) / speed;
+            let along = (*origin + *dir * time - normal * radius - start).dot(edge) / edge.length_squared();
+            if (0.0..=1.0).contains(&time) && (0.0..=1.0).contains(&along) && best.is_none_or(|(t, _)| time < t) {
+                best = Some((time, normal));
+            }
+        }
+
+        // the rounded corner
+        if radius > 0.0 {
+            if let Some((time, normal)) = ray_circle_cast(origin, dir, &start, radius) {
+                if best.is_none_or(|(t, _)| time < t) {
+                    best = Some((time, normal));
+                }
+            }
+        }
+    }
+    best
+}
+
+// swept separating axis test, on every axis find the window of time the projections overlap in
+// the shapes touch when every window is open at once, so the latest opening is when they hit
+fn sat_casted_collision(points1: &Vec<Vec2>, points2: &Vec<Vec2>, motion: &Vec2) -> Option<CastHit> {
+    let mut enter = f32::MIN;
+    let mut exit = f32::MAX;
+    let mut normal = Vec2::ZERO;
+    for axis in get_axes(points1).into_iter().chain(get_axes(points2)) {
+        let range1 = project_shape(points1, &axis);
+        let range2 = project_shape(points2, &axis);
+        let speed = axis.dot(*motion);
+        let (start, end) = if range1.1 < range2.0 {
+            if speed <= 0.0 {
+                return None;
+            }
+            ((range2.0 - range1.1) / speed, (range2.1 - range1.0) / speed)
+        } else if range2.1 < range1.0 {
+            if speed >= 0.0 {
+                return None;
+            }
+            ((range2.1 - range1.0) / speed, (range2.0 - range1.1) / speed)
+        } else if speed > 0.0 {
+            (f32::MIN, (range2.1 - range1.0) / speed)
+        } else if speed < 0.0 {
+            (f32::MIN, (range2.0 - range1.1) / speed)
+        } else {
+            (f32::MIN, f32::MAX)
+        };
+
+        if start > enter {
+            enter = start;
+            normal = if speed > 0.0 { axis } else { -axis };
+        }
+        exit = exit.min(end);
+        if enter > exit || enter > 1.0 {
+            return None;
+        }
+    }
+    Some(CastHit { time: enter.max(0.0), normal })
+}
+
+// where and how deep two shapes overlap
+// the normal points from the first shape towards the second, so moving the second
+// shape by normal * depth (or the first by -normal * depth) pulls them apart
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Contact {
+    pub normal: Vec2,
+    pub depth: f32,
+    pub points: Vec<Vec2>,
+}
+
+impl Contact {
+    // the same contact seen from the second shape
+    pub fn flipped(self) -> Contact {
+        Contact { normal: -self.normal, ..self }
+    }
+}
+
+// the most pieces we split a sweep into, a really fast spin gets less exact instead of slower
+pub const MAX_CCD_STEPS: u32 = 16;
+
+// how far from the center the furthest part of the shape is
+fn bounding_radius(shape: &Shape) -> f32 {
+    match shape {
+        Shape::Rect(size) => size.length() * 0.5,
+        Shape::Circle(radius) => *radius,
+        Shape::Poly(points) => points.iter().fold(0.0, |max, point| point.length().max(max)),
+    }
+}
+
+// the thinnest the shape gets, roughly, anything moving less than half of this can't pass through it
+fn thinnest_extent(shape: &Shape) -> f32 {
+    match shape {
+        Shape::Rect(size) => size.min_element(),
+        Shape::Circle(radius) => radius * 2.0,
+        Shape::Poly(_) => {
+            let aabb = Aabb::from_shape(&Vec2::ZERO, shape, 0.0);
+            (aabb.max - aabb.min).min_element()
+        }
+    }
+}
+
+// like detect_collision_pair but the shapes move from their from pose to their to pose (center and angle)
+// over the tick, returns the contact where they first touch
+// each piece of the sweep is cast exactly for the movement, the spinning is only sampled at the
+// start of each piece so we cut it into enough pieces that nothing turns further than it is thin
+pub fn swept_collision_pair(
+    shape1: &Shape, from1: (Vec2, f32), to1: (Vec2, f32),
+    shape2: &Shape, from2: (Vec2, f32), to2: (Vec2, f32)) -> Option<Contact>
+{
+    let spin = (to1.1 - from1.1).abs() * bounding_radius(shape1) + (to2.1 - from2.1).abs() * bounding_radius(shape2);
+    let thinnest = thinnest_extent(shape1).min(thinnest_extent(shape2)).max(MU);
+    let steps = ((spin / (thinnest * 0.5)).ceil() as u32).clamp(1, MAX_CCD_STEPS);
+
+    let motion = ((to1.0 - from1.0) - (to2.0 - from2.0)) / steps as f32;
+    for i in 0..steps {
+        let t = i as f32 / steps as f32;
+        let pos1 = from1.0.lerp(to1.0, t);
+        let pos2 = from2.0.lerp(to2.0, t);
+        let angle1 = from1.1 + (to1.1 - from1.1) * t;
+        let angle2 = from2.1 + (to2.1 - from2.1) * t;
+        let Some(hit) = shapes_casted_collision(&pos1, shape1, angle1, &pos2, shape2, angle2, &motion) else {
+            continue;
+        };
+        // where they both are when they touch, then nudge them a little further in
+        // so we get the contact points from the normal check
+        let t = (i as f32 + hit.time) / steps as f32;
+        let pos1 = from1.0.lerp(to1.0, t);
+        let pos2 = from2.0.lerp(to2.0, t);
+        return detect_collision_pair(&(pos1 + hit.normal * PENETRATION_SLOP), shape1, angle1, &pos2, shape2, angle2)
+            .or(Some(Contact { normal: hit.normal, depth: 0.0, points: vec![(pos1 + pos2) * 0.5] }));
+    }
+    None
+}
+
+// pos1 and pos2 are the center of the shapes
+pub fn detect_collision_pair(
+    pos1: &Vec2, shape1: &Shape, angle1: f32,
+    pos2: &Vec2, shape2: &Shape, angle2: f32) -> Option<Contact>
+{
+    if shape1.is_degenerate() || shape2.is_degenerate() {
+        return None;
+    }
+    match (shape1, shape2) {
+        (Shape::Rect(size1), Shape::Rect(size2)) if angle1.abs() < MU && angle2.abs() < MU => {
+            aabb_collision(&upper_left(pos1, size1), size1, &upper_left(pos2, size2), size2)
+        }
+        (Shape::Circle(radius1), Shape::Circle(radius2)) => {
+            circles_collision(pos1, radius1, pos2, radius2)
+        }
+        (Shape::Circle(radius), Shape::Rect(size)) if angle2.abs() < MU => {
+            aabb_circle_collision(&upper_left(pos2, size), size, pos1, *radius).map(Contact::flipped)
+        }
+        (Shape::Rect(size), Shape::Circle(radius)) if angle1.abs() < MU => {
+            aabb_circle_collision(&upper_left(pos1, size), size, pos2, *radius)
+        }
+        (Shape::Circle(radius), _) => {
+            sat_circle_collision(shape_points(pos2, shape2, angle2), pos1, *radius).map(Contact::flipped)
+        }
+        (_, Shape::Circle(radius)) => {
+            sat_circle_collision(shape_points(pos1, shape1, angle1), pos2, *radius)
+        }
+        (_, _) => {
+            sat_collision(shape_points(pos1, shape1, angle1), shape_points(pos2, shape2, angle2))
+        }
+    }
+}
+
+// the corners of a rect or poly in world space
+pub fn shape_points(pos: &Vec2, shape: &Shape, angle: f32) -> Vec<Vec2> {
+    match shape {
+        Shape::Rect(size) => generate_rectangle_points(pos, size, angle),
+        Shape::Poly(points) => generate_poly_points(pos, points, angle),
+        Shape::Circle(_) => unreachable!("circles don't have corners"),
+    }
+}
+
+fn upper_left(center: &Vec2, size: &Vec2) -> Vec2 {
+    Vec2 { x: center.x - size.x * 0.5, y: center.y + size.y * 0.5 }
+}
+
+// TODO Shoudl I inline these fucntions
+// Check for a collision between two circles
+fn circles_collision(pos1: &Vec2, radius1: &f32, pos2: &Vec2, radius2: &f32) -> Option<Contact> {
+    let dist = pos1.distance(*pos2);
+    if dist >= radius1 + radius2 {
+        return None;
+    }
+    // if the centers are on top of each other any direction works, pick up
+    let normal = if dist > MU { (*pos2 - *pos1) / dist } else { Vec2::Y };
+    let depth = radius1 + radius2 - dist;
+    Some(Contact {
+        normal,
+        depth,
+        points: vec![*pos1 + normal * (radius1 - depth * 0.5)],
+    })
+}
+
+// Check for a collision between axis aligned bounding boxes (two rectangles with no rotation)
+// takes in the upper left corner of the rect
+fn aabb_collision(pos1: &Vec2, size1: &Vec2, pos2: &Vec2, size2: &Vec2) -> Option<Contact> {
+    let left = pos1.x.max(pos2.x);
+    let right = (pos1.x + size1.x).min(pos2.x + size2.x);
+    let top = pos1.y.min(pos2.y);
+    let bottom = (pos1.y - size1.y).max(pos2.y - size2.y);
+    if right < left || top < bottom {
+        return None;
+    }
+
+    // push out along whichever axis has the least overlap
+    let center_diff = (*pos2 + Vec2::new(size2.x, -size2.y) * 0.5) - (*pos1 + Vec2::new(size1.x, -size1.y) * 0.5);
+    if right - left < top - bottom {
+        let x = (left + right) * 0.5;
+        Some(Contact {
+            normal: Vec2::new(if center_diff.x < 0.0 { -1.0 } else { 1.0 }, 0.0),
+            depth: right - left,
+            points: vec![Vec2::new(x, top), Vec2::new(x, bottom)],
+        })
+    } else {
+        let y = (top + bottom) * 0.5;
+        Some(Contact {
+            normal: Vec2::new(0.0, if center_diff.y < 0.0 { -1.0 } else { 1.0 }),
+            depth: top - bottom,
+            points: vec![Vec2::new(left, y), Vec2::new(right, y)],
+        })
+    }
+}
+
+// Check for a collision between axis aligned bounding box and a circle
+// the normal points from the rect to the circle
+fn aabb_circle_collision(rect_pos: &Vec2, size: &Vec2, circ_pos: &Vec2, radius: f32) -> Option<Contact> {
+    let closest = Vec2 {
+        x: circ_pos.x.clamp(rect_pos.x, rect_pos.x + size.x),
+        y: circ_pos.y.clamp(rect_pos.y - size.y, rect_pos.y),
+    };
+    let dist = closest.distance(*circ_pos);
+    if dist > MU {
+        if dist >= radius {
+            return None;
+        }
+        return Some(Contact {
+            normal: (*circ_pos - closest) / dist,
+            depth: radius - dist,
+            points: vec![closest],
+        });
+    }
+
+    // the center of the circle is inside the rect, push it out the nearest side
+    let sides = [
+        (circ_pos.x - rect_pos.x, Vec2::NEG_X),
+        (rect_pos.x + size.x - circ_pos.x, Vec2::X),
+        (rect_pos.y - circ_pos.y, Vec2::Y),
+        (circ_pos.y - (rect_pos.y - size.y), Vec2::NEG_Y),
+    ];
+    let (dist, normal) = sides.into_iter()
+        .min_by(|a, b| a.0.total_cmp(&b.0))
+        .unwrap();
+    Some(Contact {
+        normal,
+        depth: dist + radius,
+        points: vec![*circ_pos + normal * dist],
+    })
+}
+
+fn get_axes(points: &Vec<Vec2>) -> Vec<Vec2> {
+    let mut norms: Vec<Vec2> = Vec::new();
+    for i in 0..points.len() {
+        norms.push(points[i] - points[(i+1) % points.len()]);
+    }
+    norms.iter_mut().map(|x| x.perp().normalize_or_zero()).filter(|x| *x != Vec2::ZERO).collect()
+}
+
+fn project_shape(points: &Vec<Vec2>, axis: &Vec2) -> (f32,f32) {
+    let mut min: f32 = axis.dot(points[0]);
+    let mut max: f32 = min;
+    for i in 1..points.len() {
+        let val = axis.dot(points[i]);
+        if val < min {
+            min = val;
+        } else if val > max {
+            max = val;
+        }
+    }
+    return (min, max)
+}
+
+fn centroid(points: &[Vec2]) -> Vec2 {
+    if points.is_empty() {
+        return Vec2::ZERO;
+    }
+    points.iter().copied().sum::<Vec2>() / points.len() as f32
+}
+
+// the normal is the axis the shapes overlap the least on
+fn sat_collision(points1: Vec<Vec2>, points2: Vec<Vec2>) -> Option<Contact> {
+    let mut normal = Vec2::ZERO;
+    let mut depth = f32::MAX;
+    for axis in get_axes(&points1).into_iter().chain(get_axes(&points2)) {
+        let range1 = project_shape(&points1, &axis);
+        let range2 = project_shape(&points2, &axis);
+        if range1.1 < range2.0 || range2.1 < range1.0 {
+            return None;
+        }
+        let overlap = range1.1.min(range2.1) - range1.0.max(range2.0);
+        if overlap < depth {
+            depth = overlap;
+            normal = axis;
+        }
+    }
+
+    if normal.dot(centroid(&points2) - centroid(&points1)) < 0.0 {
+        normal = -normal;
+    }
+    let points = sat_contact_points(&points1, &points2, normal);
+    Some(Contact { normal, depth, points })
+}
+
+// the edge of the polygon that faces dir the most
+fn best_edge(points: &[Vec2], dir: Vec2) -> (Vec2, Vec2) {
+    let len = points.len();
+    let index = (0..len)
+        .max_by(|a, b| points[*a].dot(dir).total_cmp(&points[*b].dot(dir)))
+        .unwrap();
+    let vertex = points[index];
+    let prev = points[(index + len - 1) % len];
+    let next = points[(index + 1) % len];
+    if (vertex - prev).normalize_or_zero().dot(dir).abs() <= (vertex - next).normalize_or_zero().dot(dir).abs() {
+        (prev, vertex)
+    } else {
+        (vertex, next)
+    }
+}
+
+// keep the part of the segment that is past o along dir
+fn clip_segment(v1: Vec2, v2: Vec2, dir: Vec2, o: f32) -> Vec<Vec2> {
+    let mut clipped = Vec::new();
+    let d1 = dir.dot(v1) - o;
+    let d2 = dir.dot(v2) - o;
+    if d1 >= 0.0 {
+        clipped.push(v1);
+    }
+    if d2 >= 0.0 {
+        clipped.push(v2);
+    }
+    if d1 * d2 < 0.0 {
+        clipped.push(v1 + (v2 - v1) * (d1 / (d1 - d2)));
+    }
+    clipped
+}
+
+// the edge that lines up best with the other shape is the reference face, clip the other
+// shape's edge (the incident edge) against it and whats left inside the reference shape
+// are the contact points
+fn sat_contact_points(points1: &[Vec2], points2: &[Vec2], normal: Vec2) -> Vec<Vec2> {
+    let edge1 = best_edge(points1, normal);
+    let edge2 = best_edge(points2, -normal);
+    let (reference, incident, ref_normal) =
+        if (edge1.1 - edge1.0).normalize_or_zero().dot(normal).abs() <= (edge2.1 - edge2.0).normalize_or_zero().dot(normal).abs() {
+            (edge1, edge2, normal)
+        } else {
+            (edge2, edge1, -normal)
+        };
+    let ref_dir = (reference.1 - reference.0).normalize_or_zero();
+
+    let mut clipped = clip_segment(incident.0, incident.1, ref_dir, ref_dir.dot(reference.0));
+    if clipped.len() == 2 {
+        clipped = clip_segment(clipped[0], clipped[1], -ref_dir, -ref_dir.dot(reference.1));
+    }
+    let face = ref_normal.dot(reference.0).max(ref_normal.dot(reference.1));
+    clipped.retain(|point| ref_normal.dot(*point) <= face + MU);
+
+    if clipped.is_empty() {
+        // fall back to the deepest point of the second shape
+        let deepest = points2.iter()
+            .min_by(|a, b| normal.dot(**a).total_cmp(&normal.dot(**b)))
+            .unwrap();
+        clipped.push(*deepest);
+    }
+    clipped
+}
+
+// the normal points from the polygon to the circle
+// on top of the polygon's own axes we need the one from its closest corner to the circle
+// otherwise a circle sitting just off a corner looks like it hits
+fn sat_circle_collision(points: Vec<Vec2>, circ_pos: &Vec2, radius: f32) -> Option<Contact> {
+    let closest = points.iter()
+        .min_by(|a, b| a.distance_squared(*circ_pos).total_cmp(&b.distance_squared(*circ_pos)))
+        .unwrap();
+    let corner_axis = (*circ_pos - *closest).normalize_or_zero();
+
+    let mut normal = Vec2::ZERO;
+    let mut depth = f32::MAX;
+    for axis in get_axes(&points).into_iter().chain(std::iter::once(corner_axis)) {
+        if axis == Vec2::ZERO {
+            continue;
+        }
+        let range1 = project_shape(&points, &axis);
+        let center = axis.dot(*circ_pos);
+        let range2 = (center - radius, center + radius);
+        if range1.1 < range2.0 || range2.1 < range1.0 {
+            return None;
+        }
+        let overlap = range1.1.min(range2.1) - range1.0.max(range2.0);
+        if overlap < depth {
+            depth = overlap;
+            normal = axis;
+        }
+    }
+
+    if normal.dot(*circ_pos - centroid(&points)) < 0.0 {
+        normal = -normal;
+    }
+    Some(Contact {
+        normal,
+        depth,
+        points: vec![*circ_pos - normal * (radius - depth * 0.5)],
+    })
+}
+
+fn generate_rectangle_points(pos: &Vec2, size: &Vec2, angle: f32) -> Vec<Vec2> {
+    let hori_offset = (size.x / 2.0) * Vec2::from_angle(angle);
+    let vert_offset = (size.y / 2.0) * Vec2::from_angle(angle).perp();
+    vec![*pos - hori_offset + vert_offset,
+        *pos + hori_offset + vert_offset,
+        *pos + hori_offset - vert_offset,
+        *pos - hori_offset - vert_offset] 
+}
+
+// poly points are relative to the center of the body and have to be convex
+fn generate_poly_points(pos: &Vec2, points: &[Vec2], angle: f32) -> Vec<Vec2> {
+    let rot = Vec2::from_angle(angle);
+    points.iter().map(|point| *pos + rot.rotate(*point)).collect()
+}
+
+
+// lets check if they are on screen for now
+// so we can ignore things that aren't
+// with nothing to measure from everything is in range
+fn in_active_range(pos: &Vec2, anchors: &[Vec2], range: f32) -> bool {
+    anchors.is_empty() || anchors.iter().any(|anchor| anchor.distance_squared(*pos) <= range * range)
+}
+
+type SleepyBodies<'w, 's> = Query<'w, 's,
+    (Entity, &'static GlobalPosition, &'static Position, Option<&'static Rotation>, Option<&'static Shape>,
+        &'static mut Velocity, Option<&'static mut AngularVelocity>, &'static mut CanSleep, Option<&'static Sleeping>),
+    Without<Wall>>;
+type MovingWalls<'w, 's> = Query<'w, 's,
+    (&'static Position, &'static Shape, Option<&'static Rotation>, &'static Velocity),
+    (With<Wall>, Without<WallCollider>)>;
+
+// puts bodies to sleep and wakes them back up, the changes land at the end of the tick
+pub fn update_sleep(
+    mut commands: Commands,
+    config: Res<PhysicsConfig>,
+    mut started: EventReader<CollisionStarted>,
+    anchor_query: Query<&GlobalPosition, With<ActiveRangeAnchor>>,
+    wall_query: MovingWalls,
+    mut query: SleepyBodies
+) {
+    let anchors: Vec<Vec2> = anchor_query.iter().map(|pos| pos.0).collect();
+    // sleeping things only get paired with awake ones so any new contact means something ran into them
+    let bumped: HashSet<Entity> = started.iter().flat_map(|started| [started.0, started.1]).collect();
+    // walls aren't colliders so they never start a collision, instead anything a moving wall
+    // could reach in the next couple of ticks gets woken up before the wall gets there
+    // the wake lands at the end of the tick so one tick of sweep would be too late
+    let wall_sweeps: Vec<Aabb> = wall_query.iter()
+        .filter(|(.., vel)| vel.0 != Vec2::ZERO)
+        .map(|(pos, shape, rot, vel)| {
+            let angle = rot.map_or(0.0, |rot| rot.0);
+            let aabb = Aabb::from_shape(&pos.0, shape, angle)
+                .union(&Aabb::from_shape(&(pos.0 + vel.0 * 2.0 * config.time_step), shape, angle));
+            Aabb { min: aabb.min - Vec2::splat(config.skin_width), max: aabb.max + Vec2::splat(config.skin_width) }
+        })
+        .collect();
+    for (entity, global_pos, pos, rot, shape, mut vel, ang_vel, mut can_sleep, sleeping) in query.iter_mut() {
+        let in_range = in_active_range(&global_pos.0, &anchors, config.active_range);
+        let spin = ang_vel.as_ref().map_or(0.0, |ang_vel| ang_vel.0.abs());
+        let still = vel.0.length() < config.sleep_speed && spin < config.sleep_speed;
+        match sleeping {
+            None => {
+                if !in_range {
+                    // keep the velocity so it picks up where it left off
+                    commands.entity(entity).insert(Sleeping::OutOfRange);
+                } else if still {
+                    can_sleep.still_ticks += 1;
+                    if can_sleep.still_ticks >= config.sleep_ticks {
+                        vel.0 = Vec2::ZERO;
+                        if let Some(mut ang_vel) = ang_vel {
+                            ang_vel.0 = 0.0;
+                        }
+                        commands.entity(entity).insert(Sleeping::AtRest);
+                    }
+                } else {
+                    can_sleep.still_ticks = 0;
+                }
+            }
+            Some(Sleeping::AtRest) => {
+                if !in_range {
+                    commands.entity(entity).insert(Sleeping::OutOfRange);
+                } else if !still || bumped.contains(&entity) || shape.is_some_and(|shape| {
+                    // walls and the bodies they push share a parent, same as in handle_wall_collisions
+                    let aabb = Aabb::from_shape(&pos.0, shape, rot.map_or(0.0, |rot| rot.0));
+                    wall_sweeps.iter().any(|sweep| sweep.intersects(&aabb))
+                }) {
+                    can_sleep.still_ticks = 0;
+                    commands.entity(entity).remove::<Sleeping>();
+                }
+            }
+            Some(Sleeping::OutOfRange) => {
+                if in_range {
+                    can_sleep.still_ticks = 0;
+                    commands.entity(entity).remove::<Sleeping>();
+                }
+            }
+        }
+    }
+}
+
+// the width and height of a single cell in the broad phase grid,
+// should be about the size of the bodies we expect so most only touch a few cells
+pub const BROAD_PHASE_CELL_SIZE: f32 = 2.0;
+
+// axis aligned bounding box used by the broad phase to find pairs to check
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub min: Vec2,
+    pub max: Vec2,
+}
+
+impl Aabb {
+    // pos is the center of the shape, same as Position
+    pub fn from_shape(pos: &Vec2, shape: &Shape, angle: f32) -> Aabb {
+        match shape {
+            Shape::Rect(size) => {
+                let (sin, cos) = angle.sin_cos();
+                let half = Vec2 {
+                    x: (size.x * cos).abs() * 0.5 + (size.y * sin).abs() * 0.5,
+                    y: (size.x * sin).abs() * 0.5 + (size.y * cos).abs() * 0.5,
+                };
+                Aabb { min: *pos - half, max: *pos + half }
+            }
+            Shape::Circle(radius) => {
+                Aabb { min: *pos - Vec2::splat(*radius), max: *pos + Vec2::splat(*radius) }
+            }
+            Shape::Poly(points) => {
+                let rot = Vec2::from_angle(angle);
+                let mut aabb = Aabb { min: *pos, max: *pos };
+                for point in points.iter() {
+                    let point = *pos + rot.rotate(*point);
+                    aabb.min = aabb.min.min(point);
+                    aabb.max = aabb.max.max(point);
+                }
+                aabb
+            }
+        }
+    }
+
+    // the box around both boxes
+    pub fn union(&self, other: &Aabb) -> Aabb {
+        Aabb { min: self.min.min(other.min), max: self.max.max(other.max) }
+    }
+
+    pub fn intersects(&self, other: &Aabb) -> bool {
+        self.min.x <= other.max.x &&
+            self.max.x >= other.min.x &&
+            self.min.y <= other.max.y &&
+            self.max.y >= other.min.y
+    }
+
+    // the range of grid cells this box touches
+    fn cells(&self) -> (IVec2, IVec2) {
+        ((self.min / BROAD_PHASE_CELL_SIZE).floor().as_ivec2(),
+            (self.max / BROAD_PHASE_CELL_SIZE).floor().as_ivec2())
+    }
+}
+
+// the pairs the broad phase thinks might be touching, the narrow phase does the real check
+#[derive(Resource, Default, Debug)]
+pub struct BroadPhasePairs(pub Vec<(Entity, Entity)>);
+
+// a hash of every Position, Velocity and Rotation at the end of the last tick
+// the same inputs should give the same hash every tick, so replays and tests can check the simulation didn't drift
+#[derive(Resource, Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PhysicsChecksum {
+    pub tick: u64,
+    pub hash: u64,
+}
+
+type ChecksumBodies<'w, 's> = Query<'w, 's,
+    (Entity, Option<&'static Position>, Option<&'static Velocity>, Option<&'static Rotation>),
+    Or<(With<Position>, With<Velocity>, With<Rotation>)>>;
+
+pub fn update_checksum(
+    mut checksum: ResMut<PhysicsChecksum>,
+    query: ChecksumBodies
+) {
+    let mut bodies: Vec<_> = query.iter().collect();
+    bodies.sort_by_key(|(entity, ..)| *entity);
+    // DefaultHasher::new always starts from the same keys unlike the hash maps
+    let mut hasher = DefaultHasher::new();
+    for (entity, pos, vel, rot) in bodies {
+        entity.hash(&mut hasher);
+        for value in [pos.map(|pos| pos.0.x), pos.map(|pos| pos.0.y), vel.map(|vel| vel.0.x), vel.map(|vel| vel.0.y), rot.map(|rot| rot.0)] {
+            value.map(f32::to_bits).hash(&mut hasher);
+        }
+    }
+    checksum.tick += 1;
+    checksum.hash = hasher.finish();
+}
+
+type BroadPhaseColliders<'w, 's> = Query<'w, 's,
+    (Entity, &'static GlobalPosition, &'static GlobalRotation, &'static Shape, Option<&'static CollisionGroups>,
+        Option<&'static Ccd>, Option<&'static Sleeping>),
+    With<Collider>>;
+
+// the concept here is to decte paris to check
+// we drop every collider into a uniform spatial hash grid and only pair up
+// colliders that share a cell, so the cost grows with how crowded things are
+// instead of with the square of the number of colliders
+pub fn broad_phase(
+    query: BroadPhaseColliders,
+    mut pairs: ResMut<BroadPhasePairs>,
+    mut grid: Local<HashMap<IVec2, Vec<usize>>>,
+) {
+    pairs.0.clear();
+    for cell in grid.values_mut() {
+        cell.clear();
+    }
+
+    // things out of range aren't in the grid at all, so only the area around the action costs anything
+    let boxes: Vec<(Entity, Aabb, CollisionGroups, bool)> = query.iter()
+        .filter(|(.., sleeping)| sleeping.copied() != Some(Sleeping::OutOfRange))
+        .map(|(entity, pos, rot, shape, groups, ccd, sleeping)| {
+            let mut aabb = Aabb::from_shape(&pos.0, shape, rot.0);
+            // swept colliders need a box around the whole sweep
+            if let Some((last_pos, last_rot)) = ccd.and_then(|ccd| ccd.last_pose) {
+                aabb = aabb.union(&Aabb::from_shape(&last_pos, shape, last_rot));
+            }
+            (entity, aabb, groups.copied().unwrap_or_default(), sleeping.is_some())
+        })
+        .collect();
+
+    for (i, (_, aabb, _, _)) in boxes.iter().enumerate() {
+        let (min, max) = aabb.cells();
+        for x in min.x..=max.x {
+            for y in min.y..=max.y {
+                grid.entry(IVec2 { x, y }).or_default().push(i);
+            }
+        }
+    }
+
+    for (cell, indices) in grid.iter() {
+        for (n, &i) in indices.iter().enumerate() {
+            for &j in indices[n+1..].iter() {
+                let (entity1, aabb1, groups1, sleeping1) = &boxes[i];
+                let (entity2, aabb2, groups2, sleeping2) = &boxes[j];
+                // two sleeping things can't do anything to each other
+                if (*sleeping1 && *sleeping2) || !groups1.interacts_with(groups2) || !aabb1.intersects(aabb2) {
+                    continue;
+                }
+                // two boxes can share more than one cell, only report the pair
+                // from the lowest cell they have in common so we don't get duplicates
+                let (min1, _) = aabb1.cells();
+                let (min2, _) = aabb2.cells();
+                if *cell == min1.max(min2) {
+                    pairs.0.push((*entity1, *entity2));
+                }
+            }
+        }
+    }
+
+    // the grid is a hash map so the pairs come out in a different order every run
+    pairs.0.sort();
+
+    // drop the cells nothing used this tick so the grid doesn't grow forever as things move
+    grid.retain(|_, indices| !indices.is_empty());
+}
+
+// here is where we check the pairs
+// how shoudl we do this passing pairs to this? events?
+// directly passing the pairs as a vector of tuples somehow?
+
+//how do we act on the pairs, a big match statment? somehow calling a function of each entity and matchign there?
+/* 
+match
+    player, enemy
+    enemy, player
+
+or
+
+enemy.collider(player)
+player.collider(enemy)
+
+enemy
+    collider() {
+        match
+            player
+            hazard
     }
 
 */
+// sent the first tick two colliders touch
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CollisionStarted(pub Entity, pub Entity);
+
+// sent every tick after the first that two colliders are still touching
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CollisionStayed(pub Entity, pub Entity);
+
+// sent the first tick two colliders stop touching (or one of them is gone)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CollisionEnded(pub Entity, pub Entity);
+
+// every pair of colliders touching as of the last narrow phase
+// pairs are stored with the smaller entity first so (a, b) and (b, a) are the same contact
+// the contact normal points from the first entity of the pair to the second
+#[derive(Resource, Default, Debug, Clone)]
+pub struct CollidingPairs(pub HashMap<(Entity, Entity), Contact>);
+
+impl CollidingPairs {
+    // the map comes out in a different order every run, go through this whenever the order matters
+    pub fn sorted(&self) -> Vec<((Entity, Entity), &Contact)> {
+        let mut pairs: Vec<((Entity, Entity), &Contact)> = self.0.iter().map(|(pair, contact)| (*pair, contact)).collect();
+        pairs.sort_by_key(|(pair, _)| *pair);
+        pairs
+    }
+}
+
+// a collider that never pushes anything, it just tells us when things go in and out of it
+// which colliders count goes by its CollisionGroups like everything else, TriggerBundle::new
+// puts it on TRIGGER_LAYER so only things that hit that layer can set it off
+#[derive(Component, Default, Debug)]
+pub struct Trigger;
+
+#[derive(Bundle, Default)]
+pub struct TriggerBundle {
+    pub trigger: Trigger,
+    pub collider: Collider,
+    pub collision_groups: CollisionGroups,
+    pub body: Body,
+}
+
+impl TriggerBundle {
+    // layers are the layers that can set it off, PLAYER_LAYER for a checkpoint only the player can use
+    pub fn new(position: Vec2, shape: Shape, layers: u32) -> TriggerBundle {
+        TriggerBundle {
+            collision_groups: CollisionGroups::new(TRIGGER_LAYER, layers),
+            body: Body {
+                position: Position(position),
+                shape,
+                ..default()
+            },
+            ..default()
+        }
+    }
+}
+
+// sent the tick entity first overlaps trigger
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TriggerEntered {
+    pub trigger: Entity,
+    pub entity: Entity,
+}
+
+// sent the tick entity stops overlapping trigger, or is despawned while inside it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TriggerExited {
+    pub trigger: Entity,
+    pub entity: Entity,
+}
+
+fn ordered_pair(entity1: Entity, entity2: Entity) -> (Entity, Entity) {
+    if entity1 <= entity2 {
+        (entity1, entity2)
+    } else {
+        (entity2, entity1)
+    }
+}
+
 // for now keep it simple
-pub fn narrow_phase(query: Query<(Entity, &Shape, &GlobalTransform, &Collider)>) {
+type NarrowPhaseColliders<'w, 's> = Query<'w, 's,
+    (&'static GlobalPosition, &'static GlobalRotation, &'static Shape, Option<&'static Ccd>, Option<&'static Sleeping>),
+    With<Collider>>;
+
+pub fn narrow_phase(
+    pairs: Res<BroadPhasePairs>,
+    query: NarrowPhaseColliders,
+    mut colliding: ResMut<CollidingPairs>,
+    mut started: EventWriter<CollisionStarted>,
+    mut stayed: EventWriter<CollisionStayed>,
+    mut ended: EventWriter<CollisionEnded>,
+) {
     eprintln!("narrow_phase");
-    for [(entity1, shape1, transform1, collider1), (entity2, shape2, &transform2, collider2)] in query.iter_combinations() {
+    let mut current = CollidingPairs::default();
+    for (entity1, entity2) in pairs.0.iter() {
         //print!("{:?}, {:?}\n", entity1, entity2);
-        let trans1 = transform1.to_scale_rotation_translation();
-        let trans2 = transform2.to_scale_rotation_translation();
-        if detect_collision_pair(&(trans1.2.truncate()), shape1, trans2.1.z, &(trans2.2.truncate()), shape2, trans2.1.z) {
-            print!("collided \n");
+        let (entity1, entity2) = ordered_pair(*entity1, *entity2);
+        if let Ok([(pos1, rot1, shape1, ccd1, _), (pos2, rot2, shape2, ccd2, _)]) = query.get_many([entity1, entity2]) {
+            let to1 = (pos1.0, rot1.0);
+            let to2 = (pos2.0, rot2.0);
+            let from1 = ccd1.and_then(|ccd| ccd.last_pose);
+            let from2 = ccd2.and_then(|ccd| ccd.last_pose);
+            // a collider without ccd counts as sitting still where it ended up
+            let contact = if from1.is_some() || from2.is_some() {
+                swept_collision_pair(shape1, from1.unwrap_or(to1), to1, shape2, from2.unwrap_or(to2), to2)
+            } else {
+                detect_collision_pair(&pos1.0, shape1, rot1.0, &pos2.0, shape2, rot2.0)
+            };
+            if let Some(contact) = contact {
+                current.0.insert((entity1, entity2), contact);
+            }
+        }
+    }
+    // the broad phase leaves out pairs that are both asleep or out of range, they haven't
+    // moved apart so they're still touching the way they were when they dropped off
+    for ((entity1, entity2), contact) in colliding.sorted() {
+        if let Ok([(.., sleeping1), (.., sleeping2)]) = query.get_many([entity1, entity2]) {
+            let out_of_range = sleeping1.copied() == Some(Sleeping::OutOfRange) || sleeping2.copied() == Some(Sleeping::OutOfRange);
+            if (sleeping1.is_some() && sleeping2.is_some()) || out_of_range {
+                current.0.entry((entity1, entity2)).or_insert_with(|| contact.clone());
+            }
         }
     }
+
+    for ((entity1, entity2), _) in current.sorted() {
+        if colliding.0.contains_key(&(entity1, entity2)) {
+            stayed.send(CollisionStayed(entity1, entity2));
+        } else {
+            started.send(CollisionStarted(entity1, entity2));
+        }
+    }
+    for ((entity1, entity2), _) in colliding.sorted() {
+        if !current.0.contains_key(&(entity1, entity2)) {
+            ended.send(CollisionEnded(entity1, entity2));
+        }
+    }
+    *colliding = current;
 }
 
+// how much overlap we let go before pushing bodies apart, stops them jittering when resting on each other
+pub const PENETRATION_SLOP: f32 = 0.01;
+// how much of the overlap we push out each tick, all of it at once makes things pop apart
+pub const PENETRATION_CORRECTION: f32 = 0.2;
+
+type CollidingBodies<'w, 's> = Query<'w, 's,
+    (&'static mut Position, &'static mut Velocity, &'static Mass, Option<&'static Restitution>),
+    (With<Collider>, Without<Sleeping>, Without<Trigger>)>;
+
+// stop touching bodies moving into each other with an impulse along the contact normal
+// and move them straight out of any overlap, the overlap fix goes on the position so it
+// doesn't stay in the velocity and fling them apart
+// if that moves something into a wall the wall casting pushes it back out next tick
+// the contacts are in world space so this assumes the bodies are right under the world
+pub fn resolve_body_collisions(
+    colliding: Res<CollidingPairs>,
+    mut query: CollidingBodies
+) {
+    // each impulse changes the velocities the next one sees so they go in a fixed order
+    for ((entity1, entity2), contact) in colliding.sorted() {
+        let Ok([(mut pos1, mut vel1, mass1, restitution1), (mut pos2, mut vel2, mass2, restitution2)]) = 
+            query.get_many_mut([entity1, entity2]) else {
+            continue;
+        };
+        let inv_mass1 = if mass1.0 > 0.0 { 1.0 / mass1.0 } else { 0.0 };
+        let inv_mass2 = if mass2.0 > 0.0 { 1.0 / mass2.0 } else { 0.0 };
+        let inv_mass_sum = inv_mass1 + inv_mass2;
+        if inv_mass_sum <= 0.0 {
+            continue;
+        }
+
+        // the normal points from 1 to 2 so a negative speed means they're moving into each other
+        let normal_speed = (vel2.0 - vel1.0).dot(contact.normal);
+        let bounce = restitution1.map_or(0.0, |r| r.0).max(restitution2.map_or(0.0, |r| r.0));
+        let wanted_speed = if normal_speed < 0.0 { -bounce * normal_speed } else { normal_speed };
+        let impulse = (wanted_speed - normal_speed).max(0.0) / inv_mass_sum;
+        vel1.0 -= contact.normal * impulse * inv_mass1;
+        vel2.0 += contact.normal * impulse * inv_mass2;
+
+        // the lighter body gets moved more
+        let push_out = (contact.depth - PENETRATION_SLOP).max(0.0) * PENETRATION_CORRECTION / inv_mass_sum;
+        pos1.0 -= contact.normal * push_out * inv_mass1;
+        pos2.0 += contact.normal * push_out * inv_mass2;
+    }
+}
+
+// turn the collision events that involve a trigger into trigger events
+pub fn report_triggers(
+    mut started: EventReader<CollisionStarted>,
+    mut ended: EventReader<CollisionEnded>,
+    trigger_query: Query<(), With<Trigger>>,
+    mut entered: EventWriter<TriggerEntered>,
+    mut exited: EventWriter<TriggerExited>,
+) {
+    for &CollisionStarted(entity1, entity2) in started.iter() {
+        if trigger_query.contains(entity1) {
+            entered.send(TriggerEntered { trigger: entity1, entity: entity2 });
+        }
+        if trigger_query.contains(entity2) {
+            entered.send(TriggerEntered { trigger: entity2, entity: entity1 });
+        }
+    }
+    for &CollisionEnded(entity1, entity2) in ended.iter() {
+        if trigger_query.contains(entity1) {
+            exited.send(TriggerExited { trigger: entity1, entity: entity2 });
+        }
+        if trigger_query.contains(entity2) {
+            exited.send(TriggerExited { trigger: entity2, entity: entity1 });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn degenerate_polys_never_collide() {
+        let rect = Shape::Rect(Vec2::ONE);
+        let circle = Shape::Circle(0.5);
+        for points in [vec![], vec![Vec2::ZERO], vec![Vec2::ZERO, Vec2::X]] {
+            let poly = Shape::Poly(points);
+            assert!(poly.is_degenerate());
+            for other in [&rect, &circle, &poly] {
+                assert!(detect_collision_pair(&Vec2::ZERO, &poly, 0.3, &Vec2::ZERO, other, 0.3).is_none());
+                assert!(detect_collision_pair(&Vec2::ZERO, other, 0.3, &Vec2::ZERO, &poly, 0.3).is_none());
+                assert!(shapes_casted_collision(&Vec2::ZERO, &poly, 0.0, &Vec2::X, other, 0.0, &Vec2::X).is_none());
+                assert!(shapes_casted_collision(&Vec2::ZERO, other, 0.0, &Vec2::X, &poly, 0.0, &Vec2::X).is_none());
+            }
+            assert!(!point_in_shape(&Vec2::ZERO, &Vec2::ZERO, &poly, 0.0));
+            assert!(ray_shape_cast(&Vec2::NEG_X, &Vec2::X, &Vec2::ZERO, &poly, 0.0).is_none());
+        }
+    }
+
+    fn overlapping_pair(world: &mut World, vel1: Vec2, vel2: Vec2) -> (Entity, Entity) {
+        let body1 = world.spawn((Position(Vec2::ZERO), Velocity(vel1), Mass(1.0), Collider)).id();
+        let body2 = world.spawn((Position(Vec2::new(0.7, 0.0)), Velocity(vel2), Mass(1.0), Collider)).id();
+        let mut colliding = CollidingPairs::default();
+        colliding.0.insert(ordered_pair(body1, body2), Contact { normal: Vec2::X, depth: 0.3, points: vec![Vec2::new(0.35, 0.0)] });
+        world.insert_resource(colliding);
+        (body1, body2)
+    }
+
+    #[test]
+    fn overlapping_bodies_at_rest_separate_without_gaining_speed() {
+        let mut world = World::new();
+        let (body1, body2) = overlapping_pair(&mut world, Vec2::ZERO, Vec2::ZERO);
+        let mut schedule = Schedule::new();
+        schedule.add_system(resolve_body_collisions);
+        schedule.run(&mut world);
+
+        assert_eq!(world.get::<Velocity>(body1).unwrap().0, Vec2::ZERO);
+        assert_eq!(world.get::<Velocity>(body2).unwrap().0, Vec2::ZERO);
+        let gap = world.get::<Position>(body2).unwrap().0.x - world.get::<Position>(body1).unwrap().0.x;
+        assert!((gap - (0.7 + (0.3 - PENETRATION_SLOP) * PENETRATION_CORRECTION)).abs() < 0.0001);
+    }
+
+    #[test]
+    fn bodies_moving_into_each_other_stop() {
+        let mut world = World::new();
+        let (body1, body2) = overlapping_pair(&mut world, Vec2::X, Vec2::NEG_X);
+        let mut schedule = Schedule::new();
+        schedule.add_system(resolve_body_collisions);
+        schedule.run(&mut world);
+
+        assert!(world.get::<Velocity>(body1).unwrap().0.abs_diff_eq(Vec2::ZERO, 0.0001));
+        assert!(world.get::<Velocity>(body2).unwrap().0.abs_diff_eq(Vec2::ZERO, 0.0001));
+    }
+
+    #[test]
+    fn broad_phase_finds_the_same_pairs_as_checking_every_pair() {
+        let mut world = World::new();
+        world.init_resource::<BroadPhasePairs>();
+        let mut spawn = |pos: Vec2, shape: Shape, angle: f32| world.spawn((
+            Body { position: Position(pos), global_position: GlobalPosition(pos), global_rotations: GlobalRotation(angle), shape, ..default() },
+            Collider,
+        )).id();
+        // a fixed seed so the bodies land in the same places every run
+        let mut seed: u32 = 12345;
+        let mut next = move || {
+            seed = seed.wrapping_mul(1664525).wrapping_add(1013904223);
+            (seed >> 8) as f32 / (1 << 24) as f32
+        };
+        let mut bodies = Vec::new();
+        for i in 0..60 {
+            let pos = Vec2::new(next() * 20.0 - 10.0, next() * 20.0 - 10.0);
+            let shape = match i % 3 {
+                0 => Shape::Rect(Vec2::new(next() * 2.0 + 0.1, next() * 2.0 + 0.1)),
+                1 => Shape::Circle(next() + 0.1),
+                // big enough to cover a bunch of cells, so plenty of pairs share more than one
+                _ => Shape::Rect(Vec2::new(next() * 6.0 + 2.0, next() * 6.0 + 2.0)),
+            };
+            bodies.push(spawn(pos, shape, next() * 6.0));
+        }
+        // just touching across the line between two cells
+        let left = spawn(Vec2::new(BROAD_PHASE_CELL_SIZE - 0.1, 31.0), Shape::Rect(Vec2::splat(0.2)), 0.0);
+        let right = spawn(Vec2::new(BROAD_PHASE_CELL_SIZE + 0.1, 31.0), Shape::Rect(Vec2::splat(0.2)), 0.0);
+        bodies.extend([left, right]);
+
+        let mut schedule = Schedule::new();
+        schedule.add_system(broad_phase);
+        schedule.run(&mut world);
+
+        let aabbs: Vec<(Entity, Aabb)> = bodies.iter().map(|entity| {
+            let body = world.entity(*entity);
+            let angle = body.get::<GlobalRotation>().unwrap().0;
+            (*entity, Aabb::from_shape(&body.get::<GlobalPosition>().unwrap().0, body.get::<Shape>().unwrap(), angle))
+        }).collect();
+        let mut expected = Vec::new();
+        for (i, (entity1, aabb1)) in aabbs.iter().enumerate() {
+            for (entity2, aabb2) in aabbs[i+1..].iter() {
+                if aabb1.intersects(aabb2) {
+                    expected.push(ordered_pair(*entity1, *entity2));
+                }
+            }
+        }
+        expected.sort();
+        let mut found: Vec<(Entity, Entity)> = world.resource::<BroadPhasePairs>().0.iter()
+            .map(|(entity1, entity2)| ordered_pair(*entity1, *entity2))
+            .collect();
+        found.sort();
+
+        assert!(expected.contains(&ordered_pair(left, right)));
+        assert!(expected.len() > 20, "only {} pairs", expected.len());
+        // and nothing in there twice
+        assert_eq!(found, expected);
+    }
+
+    #[test]
+    fn triangle_is_not_degenerate() {
+        let triangle = Shape::Poly(vec![Vec2::new(-0.5, -0.5), Vec2::new(0.5, -0.5), Vec2::new(0.0, 0.5)]);
+        assert!(!triangle.is_degenerate());
+        assert!(detect_collision_pair(&Vec2::ZERO, &triangle, 0.0, &Vec2::new(0.0, 0.4), &Shape::Circle(0.2), 0.0).is_some());
+    }
+
+    #[test]
+    fn overlapping_rects_push_out_instead_of_panicking() {
+        // a 1x1 box sunk a quarter into the top of the floor
+        let hit = rectangles_casted_collision(
+            &Vec2::new(0.0, 0.75), &Vec2::ONE, &Vec2::ZERO,
+            &Vec2::new(-5.0, 0.0), &Vec2::new(10.0, 1.0), &Vec2::ZERO,
+            1.0 / 60.0).unwrap();
+        assert_eq!(hit.time, 0.0);
+        assert_eq!(hit.normal, Vec2::NEG_Y);
+    }
+
+    #[test]
+    fn rect_landing_time_uses_the_vertical_speed() {
+        // falling 1 meter onto the floor at 2 m/s while moving sideways at 5 m/s
+        let hit = rectangles_casted_collision(
+            &Vec2::new(0.0, 2.0), &Vec2::ONE, &Vec2::new(5.0, -2.0),
+            &Vec2::new(-10.0, 0.0), &Vec2::new(20.0, 1.0), &Vec2::ZERO,
+            1.0).unwrap();
+        assert!((hit.time - 0.5).abs() < 0.0001, "hit at {}", hit.time);
+        assert_eq!(hit.normal, Vec2::NEG_Y);
+    }
+
+    #[test]
+    fn rect_sliding_over_a_seam_does_not_catch_on_it() {
+        // standing a skin width above two floor tiles that meet at x = 0 and running across the seam
+        let tile_size = Vec2::new(1.0, 1.0);
+        let box_upper_left = Vec2::new(-0.6, 0.5 + SKIN_WIDTH);
+        for tile_upper_left in [Vec2::new(-1.0, 0.0), Vec2::new(0.0, 0.0)] {
+            assert!(rectangles_casted_collision(
+                &box_upper_left, &Vec2::splat(0.5), &Vec2::new(5.0, 0.0),
+                &tile_upper_left, &tile_size, &Vec2::ZERO,
+                0.1).is_none());
+        }
+    }
+
+    fn gravity_world(zones: &[(Vec2, GravityMode, i32)]) -> World {
+        let mut world = World::new();
+        world.insert_resource(PhysicsConfig::default());
+        for (position, mode, priority) in zones {
+            let mut zone = GravityZoneBundle::new(*position, Shape::Rect(Vec2::splat(4.0)), *mode);
+            zone.zone.priority = *priority;
+            world.spawn((zone.zone, zone.body.shape, GlobalPosition(*position), GlobalRotation(0.0)));
+        }
+        world
+    }
+
+    fn pull_at(world: &mut World, position: Vec2, gravity: Gravity) -> Vec2 {
+        let body = world.spawn((Acceleration::default(), gravity, GlobalPosition(position))).id();
+        let mut schedule = Schedule::new();
+        schedule.add_system(apply_gravity);
+        schedule.run(world);
+        let accel = world.get::<Acceleration>(body).unwrap().0;
+        world.despawn(body);
+        accel
+    }
+
+    #[test]
+    fn override_zones_replace_gravity_and_the_highest_priority_wins() {
+        let up = Vec2::new(0.0, 5.0);
+        let sideways = Vec2::new(3.0, 0.0);
+        let mut world = gravity_world(&[
+            (Vec2::ZERO, GravityMode::Override(up), 0),
+            (Vec2::new(2.0, 0.0), GravityMode::Override(sideways), 1),
+        ]);
+
+        assert_eq!(pull_at(&mut world, Vec2::new(-1.0, 0.0), Gravity::default()), up);
+        // both zones cover this spot
+        assert_eq!(pull_at(&mut world, Vec2::new(1.0, 0.0), Gravity::default()), sideways);
+        assert_eq!(pull_at(&mut world, Vec2::new(10.0, 0.0), Gravity::default()), GRAVITY_VECTOR);
+    }
+
+    #[test]
+    fn additive_zones_stack_on_top_of_whatever_gravity_is_there() {
+        let wind = Vec2::new(2.0, 0.0);
+        let mut world = gravity_world(&[
+            (Vec2::ZERO, GravityMode::Add(wind), 0),
+            (Vec2::new(3.0, 0.0), GravityMode::Add(wind), 0),
+            (Vec2::new(-3.0, 0.0), GravityMode::Override(Vec2::ZERO), 0),
+        ]);
+
+        assert_eq!(pull_at(&mut world, Vec2::new(0.5, 0.0), Gravity::default()), GRAVITY_VECTOR + wind);
+        assert_eq!(pull_at(&mut world, Vec2::new(1.5, 0.0), Gravity::default()), GRAVITY_VECTOR + wind * 2.0);
+        // overriding zones only replace the gravity, not what gets added
+        assert_eq!(pull_at(&mut world, Vec2::new(-1.5, 0.0), Gravity::default()), wind);
+        // the bodies own gravity scales and turns the total
+        let scaled = Gravity { scale: 0.5, direction: Some(Vec2::X) };
+        assert!(pull_at(&mut world, Vec2::new(0.5, 0.0), scaled).abs_diff_eq(
+            Vec2::X * (GRAVITY_VECTOR + wind).length() * 0.5, 0.0001));
+    }
+
+    #[test]
+    fn buoyancy_pushes_against_the_gravity_the_body_feels() {
+        let mut world = gravity_world(&[(Vec2::ZERO, GravityMode::Override(Vec2::new(0.0, -2.0)), 0)]);
+        let fluid = world.spawn(FluidVolume::default()).id();
+        let submerged = Submerged { fraction: 1.0, fluid: Some(fluid) };
+        let shape = Shape::Rect(Vec2::ONE);
+        // density 1 with the same mass as area floats, so all the way under it cancels out gravity exactly
+        let in_zone = world.spawn((Acceleration::default(), submerged.clone(), shape.clone(), Gravity::default(),
+            GlobalPosition(Vec2::ZERO), Mass(1.0))).id();
+        let turned = world.spawn((Acceleration::default(), submerged.clone(), shape.clone(),
+            Gravity { scale: 2.0, direction: Some(Vec2::X) }, GlobalPosition(Vec2::new(10.0, 0.0)), Mass(1.0))).id();
+        let mut schedule = Schedule::new();
+        schedule.add_systems((apply_gravity, apply_buoyancy).chain());
+        schedule.run(&mut world);
+
+        assert!(world.get::<Acceleration>(in_zone).unwrap().0.abs_diff_eq(Vec2::ZERO, 0.0001));
+        assert!(world.get::<Acceleration>(turned).unwrap().0.abs_diff_eq(Vec2::ZERO, 0.0001));
+    }
+
+    // just the physics, the tests step it by running the fixed schedule themselves
+    pub(crate) fn physics_app() -> App {
+        let mut app = App::new();
+        app.add_plugin(PhysicsPlugin);
+        app
+    }
+
+    #[test]
+    fn circle_rests_on_rotated_wall_without_touching_it() {
+        let mut app = physics_app();
+        let angle = 0.3;
+        let wall_pos = Vec2::new(10.0, -10.0);
+        let wall_size = Vec2::new(60.0, 1.0);
+        app.world.spawn((Wall, Position(wall_pos), Rotation(angle), Shape::Rect(wall_size)));
+        let ball = app.world.spawn((
+            BasePhysicsBundle {
+                body: Body { position: Position(Vec2::new(10.0, -9.0)), shape: Shape::Circle(0.25), ..default() },
+                ..default()
+            },
+            Gravity::default(),
+            WallCollider,
+            WallSensor::default(),
+        )).id();
+
+        let up = Vec2::from_angle(angle).perp();
+        let mut grounded_ticks = 0;
+        for _ in 0..600 {
+            app.world.run_schedule(CoreSchedule::FixedUpdate);
+            let pos = app.world.get::<Position>(ball).unwrap().0;
+            // how far the bottom of the ball is above the top face of the wall
+            let gap = (pos - wall_pos).dot(up) - wall_size.y * 0.5 - 0.25;
+            assert!(gap > 0.0, "ball sank into the wall, {gap}");
+            assert!(detect_collision_pair(&pos, &Shape::Circle(0.25), 0.0, &wall_pos, &Shape::Rect(wall_size), angle).is_none());
+            if app.world.get::<WallSensor>(ball).unwrap().down {
+                grounded_ticks += 1;
+                assert!(gap < 0.01, "ball floated off the wall, {gap}");
+            }
+        }
+        assert!(grounded_ticks > 400, "only on the ground for {grounded_ticks} ticks");
+    }
+
+    #[test]
+    fn wall_colliders_move_their_velocity_once_per_tick() {
+        let mut app = physics_app();
+        let time_step = PhysicsConfig::default().time_step;
+        app.world.spawn((Wall, Position(Vec2::new(0.0, -0.5)), Shape::Rect(Vec2::new(40.0, 1.0))));
+        let free = app.world.spawn((
+            BasePhysicsBundle {
+                body: Body { position: Position(Vec2::new(0.0, 5.0)), ..default() },
+                velocity: Velocity(Vec2::new(3.0, 1.0)),
+                ..default()
+            },
+            WallCollider,
+        )).id();
+        // a box on the floor running across it, the part of the tick after the floor still gets moved
+        let runner = app.world.spawn((
+            BasePhysicsBundle {
+                body: Body { position: Position(Vec2::new(0.0, 0.25 + SKIN_WIDTH)), shape: Shape::Rect(Vec2::splat(0.5)), ..default() },
+                velocity: Velocity(Vec2::new(3.0, -1.0)),
+                ..default()
+            },
+            WallCollider,
+        )).id();
+        app.world.run_schedule(CoreSchedule::FixedUpdate);
+
+        let free_pos = app.world.get::<Position>(free).unwrap().0;
+        assert!(free_pos.abs_diff_eq(Vec2::new(3.0, 1.0) * time_step + Vec2::new(0.0, 5.0), 0.0001), "moved to {free_pos}");
+        let runner_pos = app.world.get::<Position>(runner).unwrap().0;
+        assert!((runner_pos.x - 3.0 * time_step).abs() < 0.0001, "moved to {runner_pos}");
+        assert!(runner_pos.y > 0.25 && runner_pos.y < 0.25 + 2.0 * SKIN_WIDTH, "moved to {runner_pos}");
+    }
+
+    #[test]
+    fn wall_sensor_picks_up_what_the_ground_is_made_of() {
+        let mut app = physics_app();
+        app.world.spawn((Wall, Position(Vec2::new(0.0, -0.5)), Shape::Rect(Vec2::new(40.0, 1.0)), SurfaceMaterial::ICE));
+        let body = app.world.spawn((
+            BasePhysicsBundle {
+                body: Body { position: Position(Vec2::new(0.0, 0.3)), shape: Shape::Rect(Vec2::splat(0.5)), ..default() },
+                ..default()
+            },
+            Gravity::default(),
+            WallCollider,
+            WallSensor::default(),
+        )).id();
+
+        for _ in 0..60 {
+            app.world.run_schedule(CoreSchedule::FixedUpdate);
+        }
+        assert_eq!(app.world.get::<WallSensor>(body).unwrap().ground_material, SurfaceMaterial::ICE);
+
+        // and forgets it once we're off it
+        app.world.get_mut::<Position>(body).unwrap().0 = Vec2::new(0.0, 10.0);
+        app.world.run_schedule(CoreSchedule::FixedUpdate);
+        assert_eq!(app.world.get::<WallSensor>(body).unwrap().ground_material, SurfaceMaterial::default());
+    }
+
+    #[test]
+    fn standing_on_a_conveyor_drags_us_along_it() {
+        let mut app = physics_app();
+        app.world.spawn((Wall, Position(Vec2::new(0.0, -0.5)), Shape::Rect(Vec2::new(40.0, 1.0)), SurfaceVelocity(2.0)));
+        let body = app.world.spawn((
+            BasePhysicsBundle {
+                body: Body { position: Position(Vec2::new(0.0, 0.3)), shape: Shape::Rect(Vec2::splat(0.5)), ..default() },
+                friction: Friction(Vec2::new(7.0, 0.0)),
+                ..default()
+            },
+            Gravity::default(),
+            WallCollider,
+            WallSensor::default(),
+        )).id();
+
+        for _ in 0..300 {
+            app.world.run_schedule(CoreSchedule::FixedUpdate);
+        }
+        // clockwise, so right along the top
+        assert_eq!(app.world.get::<WallSensor>(body).unwrap().ground_velocity, Vec2::new(2.0, 0.0));
+        let vel = app.world.get::<Velocity>(body).unwrap().0;
+        assert!((vel.x - 2.0).abs() < 0.05, "moving at {vel}");
+    }
+
+    #[test]
+    fn triggers_report_enter_and_exit_once_for_the_layers_they_watch() {
+        let mut app = physics_app();
+        let trigger = app.world.spawn(TriggerBundle::new(Vec2::ZERO, Shape::Rect(Vec2::splat(2.0)), PLAYER_LAYER)).id();
+        let mut spawn_runner = |layer: u32, y: f32| app.world.spawn((
+            BasePhysicsBundle {
+                body: Body { position: Position(Vec2::new(-3.0, y)), shape: Shape::Rect(Vec2::splat(0.5)), ..default() },
+                velocity: Velocity(Vec2::new(6.0, 0.0)),
+                ..default()
+            },
+            Collider,
+            CollisionGroups::new(layer, TRIGGER_LAYER),
+        )).id();
+        let player = spawn_runner(PLAYER_LAYER, 0.0);
+        let enemy = spawn_runner(ENEMY_LAYER, 0.5);
+
+        let mut entered_reader = app.world.resource::<Events<TriggerEntered>>().get_reader();
+        let mut exited_reader = app.world.resource::<Events<TriggerExited>>().get_reader();
+        let mut entered = Vec::new();
+        let mut exited = Vec::new();
+        let mut inside_ticks = 0;
+        for tick in 0..300 {
+            app.world.run_schedule(CoreSchedule::FixedUpdate);
+            let events = app.world.resource::<Events<TriggerEntered>>();
+            entered.extend(entered_reader.iter(events).map(|event| (tick, *event)));
+            let events = app.world.resource::<Events<TriggerExited>>();
+            exited.extend(exited_reader.iter(events).map(|event| (tick, *event)));
+            if app.world.resource::<CollidingPairs>().0.contains_key(&ordered_pair(trigger, player)) {
+                inside_ticks += 1;
+            }
+            assert!(!app.world.resource::<CollidingPairs>().0.contains_key(&ordered_pair(trigger, enemy)));
+        }
+
+        // one enter, staying inside for a while without hearing about it again, then one exit
+        assert_eq!(entered.iter().map(|(_, event)| *event).collect::<Vec<_>>(), vec![TriggerEntered { trigger, entity: player }]);
+        assert_eq!(exited.iter().map(|(_, event)| *event).collect::<Vec<_>>(), vec![TriggerExited { trigger, entity: player }]);
+        assert_eq!(exited[0].0 - entered[0].0, inside_ticks);
+        assert!(inside_ticks > 100, "only inside for {inside_ticks} ticks");
+        // and the trigger never pushed back
+        assert_eq!(app.world.get::<Velocity>(player).unwrap().0, Vec2::new(6.0, 0.0));
+    }
+
+    #[test]
+    fn ccd_colliders_are_not_swept_from_the_origin_on_their_first_tick() {
+        let mut app = physics_app();
+        let spawn = |app: &mut App, position: Vec2, shape: Shape| app.world.spawn((
+            BasePhysicsBundle {
+                body: Body { position: Position(position), shape, ..default() },
+                ..default()
+            },
+            Collider,
+        )).id();
+        // a thin post between the origin and where the ccd collider starts off
+        let post = spawn(&mut app, Vec2::new(2.5, 0.0), Shape::Rect(Vec2::new(0.1, 2.0)));
+        let head = spawn(&mut app, Vec2::new(5.0, 0.0), Shape::Circle(0.25));
+        app.world.entity_mut(head).insert(Ccd::default());
+
+        for _ in 0..3 {
+            app.world.run_schedule(CoreSchedule::FixedUpdate);
+            assert!(!app.world.resource::<CollidingPairs>().0.contains_key(&ordered_pair(post, head)));
+        }
+        assert_eq!(app.world.get::<Ccd>(head).unwrap().last_pose, Some((Vec2::new(5.0, 0.0), 0.0)));
+    }
+
+    #[test]
+    fn contacts_that_fall_asleep_or_out_of_range_do_not_end() {
+        let mut app = physics_app();
+        // no mass so nothing pushes them apart while they sit overlapping
+        let spawn = |app: &mut App, x: f32| app.world.spawn((
+            BasePhysicsBundle {
+                body: Body { position: Position(Vec2::new(x, 0.0)), shape: Shape::Rect(Vec2::ONE), ..default() },
+                mass: Mass(0.0),
+                ..default()
+            },
+            Collider,
+        )).id();
+        let first = spawn(&mut app, 0.0);
+        let second = spawn(&mut app, 0.8);
+        let mut ended_reader = app.world.resource::<Events<CollisionEnded>>().get_reader();
+        app.world.run_schedule(CoreSchedule::FixedUpdate);
+        assert!(app.world.resource::<CollidingPairs>().0.contains_key(&ordered_pair(first, second)));
+
+        for (sleeping1, sleeping2) in [(Some(Sleeping::AtRest), Some(Sleeping::AtRest)), (None, Some(Sleeping::OutOfRange))] {
+            for (entity, sleeping) in [(first, sleeping1), (second, sleeping2)] {
+                match sleeping {
+                    Some(sleeping) => { app.world.entity_mut(entity).insert(sleeping); }
+                    None => { app.world.entity_mut(entity).remove::<Sleeping>(); }
+                }
+            }
+            for _ in 0..10 {
+                app.world.run_schedule(CoreSchedule::FixedUpdate);
+                assert!(app.world.resource::<CollidingPairs>().0.contains_key(&ordered_pair(first, second)));
+            }
+        }
+        assert_eq!(ended_reader.iter(app.world.resource::<Events<CollisionEnded>>()).count(), 0);
+    }
+
+    #[test]
+    fn moving_walls_wake_up_what_they_run_into() {
+        let mut app = physics_app();
+        app.world.spawn((Wall, Position(Vec2::new(0.0, -0.5)), Shape::Rect(Vec2::new(40.0, 1.0))));
+        let wall = app.world.spawn((Wall, Position(Vec2::new(-2.0, 0.5)), Shape::Rect(Vec2::ONE), Velocity(Vec2::new(3.0, 0.0)))).id();
+        let body = app.world.spawn((
+            BasePhysicsBundle {
+                body: Body { position: Position(Vec2::new(0.0, 0.25 + SKIN_WIDTH)), shape: Shape::Rect(Vec2::splat(0.5)), ..default() },
+                ..default()
+            },
+            Gravity::default(),
+            WallCollider,
+            CanSleep::default(),
+            Sleeping::AtRest,
+        )).id();
+
+        let mut woke = false;
+        for _ in 0..300 {
+            app.world.run_schedule(CoreSchedule::FixedUpdate);
+            woke |= app.world.get::<Sleeping>(body).is_none();
+            let wall_edge = app.world.get::<Position>(wall).unwrap().0.x + 0.5;
+            let body_edge = app.world.get::<Position>(body).unwrap().0.x - 0.25;
+            assert!(body_edge >= wall_edge - 0.001, "the wall went into the body, {wall_edge} past {body_edge}");
+        }
+        assert!(woke);
+        assert!(app.world.get::<Position>(body).unwrap().0.x > 1.7);
+    }
+
+    // the same bodies spawned in the same order into a fresh world
+    fn spawn_busy_world(app: &mut App) {
+        app.world.spawn((Wall, Position(Vec2::new(0.0, -0.5)), Shape::Rect(Vec2::new(40.0, 1.0)), SurfaceMaterial::ICE));
+        app.world.spawn(GravityZoneBundle::new(Vec2::new(6.0, 3.0), Shape::Rect(Vec2::splat(4.0)), GravityMode::Override(Vec2::new(0.0, 5.0))));
+        app.world.spawn(TriggerBundle::new(Vec2::new(-3.0, 1.0), Shape::Rect(Vec2::splat(2.0)), ALL_LAYERS));
+        for i in 0..12 {
+            let x = (i % 4) as f32 * 0.7 - 1.0;
+            let y = 1.0 + (i / 4) as f32 * 0.6;
+            app.world.spawn((
+                BasePhysicsBundle {
+                    body: Body { position: Position(Vec2::new(x, y)), shape: Shape::Rect(Vec2::splat(0.5)), ..default() },
+                    velocity: Velocity(Vec2::new((i as f32 - 6.0) * 0.8, 0.0)),
+                    restitution: Restitution(0.3),
+                    ..default()
+                },
+                Gravity::default(),
+                Collider,
+                WallCollider,
+                CanSleep::default(),
+            ));
+        }
+        let bob = app.world.spawn((
+            BasePhysicsBundle {
+                body: Body { position: Position(Vec2::new(3.0, 4.0)), shape: Shape::Circle(0.3), ..default() },
+                ..default()
+            },
+            Gravity::default(),
+        )).id();
+        app.world.entity_mut(bob).insert(Joint::to_world(Vec2::new(1.0, 5.0), JointKind::Distance(2.2)));
+    }
+
+    #[test]
+    fn the_same_world_gives_the_same_checksum_every_tick() {
+        let mut apps = [physics_app(), physics_app()];
+        for app in apps.iter_mut() {
+            // any two systems that could run in either order would make the result depend on the scheduler
+            app.edit_schedule(CoreSchedule::FixedUpdate, |schedule| {
+                schedule.set_build_settings(ScheduleBuildSettings {
+                    ambiguity_detection: LogLevel::Error,
+                    ..default()
+                });
+            });
+            spawn_busy_world(app);
+        }
+        let mut hashes = HashSet::new();
+        for tick in 1..=400 {
+            let [first, second] = apps.each_mut().map(|app| {
+                app.world.run_schedule(CoreSchedule::FixedUpdate);
+                *app.world.resource::<PhysicsChecksum>()
+            });
+            assert_eq!(first.tick, tick);
+            assert_eq!(first, second, "drifted apart on tick {tick}");
+            hashes.insert(first.hash);
+        }
+        // make sure things were actually moving
+        assert!(hashes.len() > 100);
+    }
+
+    #[test]
+    fn one_way_platforms_only_stop_things_landing_on_them() {
+        let mut app = physics_app();
+        app.world.spawn((Wall, OneWayPlatform, Position(Vec2::ZERO), Shape::Rect(Vec2::new(40.0, 0.2))));
+        let mut spawn = |x: f32, y: f32, vel: f32| app.world.spawn((
+            BasePhysicsBundle {
+                body: Body { position: Position(Vec2::new(x, y)), shape: Shape::Rect(Vec2::splat(0.5)), ..default() },
+                velocity: Velocity(Vec2::new(0.0, vel)),
+                ..default()
+            },
+            Gravity::default(),
+            WallCollider,
+            WallSensor::default(),
+        )).id();
+        // jumps up through it from underneath and comes back down on top
+        let jumper = spawn(-5.0, -1.0, 6.0);
+        let faller = spawn(0.0, 2.0, 0.0);
+        let dropper = spawn(5.0, 2.0, 0.0);
+        app.world.entity_mut(dropper).insert(DropThrough::default());
+
+        for _ in 0..600 {
+            app.world.run_schedule(CoreSchedule::FixedUpdate);
+        }
+        // tick_drop_through isn't part of the physics so the dropper keeps falling through
+        for body in [jumper, faller] {
+            let y = app.world.get::<Position>(body).unwrap().0.y;
+            assert!((y - 0.35).abs() < 0.01, "ended up at {y}");
+            assert!(app.world.get::<WallSensor>(body).unwrap().down);
+        }
+        assert!(app.world.get::<Position>(dropper).unwrap().0.y < -2.0);
+        assert!(!app.world.get::<WallSensor>(dropper).unwrap().down);
+    }
+
+    #[test]
+    fn walking_down_a_ramp_stays_on_the_ground() {
+        let mut app = physics_app();
+        app.world.spawn((Wall, Position(Vec2::new(0.0, -0.5)), Shape::Rect(Vec2::new(40.0, 1.0))));
+        // a ledge with a ramp going down to the right off the end of it
+        app.world.spawn((Wall, Position(Vec2::new(-6.0, 0.75)), Shape::Rect(Vec2::new(6.0, 1.5))));
+        app.world.spawn((Wall, Position(Vec2::ZERO), Shape::Poly(vec![Vec2::new(-3.0, 0.0), Vec2::new(3.0, 0.0), Vec2::new(-3.0, 1.5)])));
+        let walker = app.world.spawn((
+            BasePhysicsBundle {
+                body: Body { position: Position(Vec2::new(-5.0, 1.75 + SKIN_WIDTH)), shape: Shape::Rect(Vec2::splat(0.5)), ..default() },
+                ..default()
+            },
+            PhysicsControllerBundle { over_vel: OverrideVelocity(Some(3.0), None), ..default() },
+            Gravity::default(),
+            WallCollider,
+            WallSensor::default(),
+            SlopeWalker::default(),
+        )).id();
+
+        let mut tick = 0;
+        while app.world.get::<Position>(walker).unwrap().0.x < 5.0 {
+            app.world.run_schedule(CoreSchedule::FixedUpdate);
+            tick += 1;
+            assert!(tick < 1000, "never got to the bottom");
+            assert!(app.world.get::<WallSensor>(walker).unwrap().down, "left the ground at {}", app.world.get::<Position>(walker).unwrap().0);
+        }
+        let y = app.world.get::<Position>(walker).unwrap().0.y;
+        assert!((y - 0.25).abs() < 0.01, "ended up at {y}");
+    }
+
+    // a ball dropped just above the middle of a ramp going up to the left at angle
+    fn slope_app(angle: f32) -> (App, Entity) {
+        let mut app = physics_app();
+        let height = 4.0 * angle.tan();
+        app.world.spawn((Wall, Position(Vec2::ZERO), Shape::Poly(vec![Vec2::new(-2.0, 0.0), Vec2::new(2.0, 0.0), Vec2::new(-2.0, height)])));
+        let ball = app.world.spawn((
+            BasePhysicsBundle {
+                body: Body { position: Position(Vec2::new(0.0, height * 0.5 + 0.25 / angle.cos() + 0.05)), shape: Shape::Circle(0.25), ..default() },
+                ..default()
+            },
+            Gravity::default(),
+            WallCollider,
+            WallSensor::default(),
+            SlopeWalker::default(),
+        )).id();
+        for _ in 0..100 {
+            app.world.run_schedule(CoreSchedule::FixedUpdate);
+        }
+        (app, ball)
+    }
+
+    #[test]
+    fn slopes_steeper_than_the_max_walkable_angle_slide() {
+        let (app, ball) = slope_app(DEFAULT_MAX_WALKABLE_ANGLE - 0.3);
+        assert!(app.world.get::<WallSensor>(ball).unwrap().down);
+        let x = app.world.get::<Position>(ball).unwrap().0.x;
+        assert!(x.abs() < 0.01, "slid to {x}");
+
+        let (app, ball) = slope_app(DEFAULT_MAX_WALKABLE_ANGLE + 0.2);
+        assert!(!app.world.get::<WallSensor>(ball).unwrap().down);
+        let x = app.world.get::<Position>(ball).unwrap().0.x;
+        assert!(x > 0.1, "only slid to {x}");
+    }
+
+    #[test]
+    fn transforms_are_drawn_between_or_ahead_of_the_ticks() {
+        let mut world = World::new();
+        let mut fixed_time = FixedTime::new_from_secs(0.1);
+        // a quarter of the way into the next tick
+        fixed_time.tick(Duration::from_secs_f32(0.025));
+        world.insert_resource(fixed_time);
+        let mut spawn = |interpolation: Interpolation| world.spawn((
+            interpolation,
+            Position(Vec2::new(2.0, 4.0)),
+            Rotation(1.0),
+            PreviousPosition(Vec2::new(1.0, 0.0)),
+            PreviousRotation(0.6),
+            Transform::from_xyz(0.0, 0.0, 3.0),
+        )).id();
+        let off = spawn(Interpolation::Off);
+        let interpolated = spawn(Interpolation::Interpolate);
+        let extrapolated = spawn(Interpolation::Extrapolate);
+        let mut schedule = Schedule::new();
+        schedule.add_system(interpolate_transforms);
+
+        // just spawned, there's no last tick to blend from yet
+        schedule.run(&mut world);
+        for entity in [off, interpolated, extrapolated] {
+            assert_eq!(world.get::<Transform>(entity).unwrap().translation, Vec3::new(2.0, 4.0, 3.0));
+        }
+
+        schedule.run(&mut world);
+        for (entity, pos, rot) in [(off, Vec2::new(2.0, 4.0), 1.0), (interpolated, Vec2::new(1.25, 1.0), 0.7), (extrapolated, Vec2::new(2.25, 5.0), 1.1)] {
+            let trans = world.get::<Transform>(entity).unwrap();
+            assert!(trans.translation.abs_diff_eq(pos.extend(3.0), 0.0001), "drawn at {}", trans.translation);
+            assert!(trans.rotation.abs_diff_eq(Quat::from_rotation_z(rot), 0.0001));
+        }
+    }
+}
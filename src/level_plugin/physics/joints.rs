@@ -0,0 +1,321 @@
+use bevy::prelude::*;
+
+use super::*;
+
+// how many times we go over all the joints each tick, chains need a few passes
+// for a pull on one end to make it down to the other
+pub const JOINT_ITERATIONS: usize = 8;
+// how much of the drift apart we fix each tick, all of it at once makes chains explode
+pub const JOINT_CORRECTION: f32 = 0.2;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum JointKind {
+    // pins the two anchors together, the bodies can still spin around it
+    Revolute,
+    // keeps the anchors exactly this far apart, like a rod
+    Distance(f32),
+    // keeps the anchors at most this far apart but lets them get closer
+    Rope(f32),
+    // pulls the anchors towards rest_length apart, stiffness is how hard and damping stops it bouncing forever
+    Spring { rest_length: f32, stiffness: f32, damping: f32 },
+}
+
+// goes on one of the two bodies and links it to the other, or to a fixed point in the world if other is none
+// anchor is relative to this bodies center and turns with it, other_anchor is relative to the other
+// bodies center or where in the world we hang from when there's no other body
+// both bodies need to have the same parent since we go off Position
+// bodies without a Velocity (or with a Mass of 0) don't get moved, AngularVelocity lets them swing
+#[derive(Component, Debug, Clone, Copy)]
+pub struct Joint {
+    pub other: Option<Entity>,
+    pub kind: JointKind,
+    pub anchor: Vec2,
+    pub other_anchor: Vec2,
+}
+
+impl Joint {
+    pub fn new(other: Entity, kind: JointKind) -> Joint {
+        Joint { other: Some(other), kind, anchor: Vec2::ZERO, other_anchor: Vec2::ZERO }
+    }
+
+    pub fn to_world(point: Vec2, kind: JointKind) -> Joint {
+        Joint { other: None, kind, anchor: Vec2::ZERO, other_anchor: point }
+    }
+
+    pub fn with_anchors(mut self, anchor: Vec2, other_anchor: Vec2) -> Joint {
+        self.anchor = anchor;
+        self.other_anchor = other_anchor;
+        self
+    }
+}
+
+// one end of a joint while we solve it, r is from the center to the anchor in world space
+struct JointEnd {
+    pos: Vec2,
+    r: Vec2,
+    vel: Vec2,
+    ang_vel: f32,
+    inv_mass: f32,
+    inv_inertia: f32,
+}
+
+impl JointEnd {
+    fn anchor(&self) -> Vec2 {
+        self.pos + self.r
+    }
+
+    // how fast the anchor is moving, counting the spin
+    fn anchor_vel(&self) -> Vec2 {
+        self.vel + self.r.perp() * self.ang_vel
+    }
+
+    fn apply_impulse(&mut self, impulse: Vec2) {
+        self.vel += impulse * self.inv_mass;
+        self.ang_vel += self.r.perp_dot(impulse) * self.inv_inertia;
+    }
+
+    // how hard it is to move the anchor along dir
+    fn inv_mass_along(&self, dir: Vec2) -> f32 {
+        let r_cross = self.r.perp_dot(dir);
+        self.inv_mass + self.inv_inertia * r_cross * r_cross
+    }
+}
+
+type JointBodies<'w, 's> = Query<'w, 's,
+    (&'static Position, &'static Rotation, &'static Shape, Option<&'static mut Velocity>, Option<&'static mut AngularVelocity>, Option<&'static Mass>)>;
+
+fn moment_of_inertia(shape: &Shape, mass: f32) -> f32 {
+    match shape {
+        Shape::Rect(size) => mass * size.length_squared() / 12.0,
+        Shape::Circle(radius) => mass * radius * radius * 0.5,
+        // treat it as the box around it, close enough for swinging things around
+        Shape::Poly(_) => {
+            let aabb = Aabb::from_shape(&Vec2::ZERO, shape, 0.0);
+            mass * (aabb.max - aabb.min).length_squared() / 12.0
+        }
+    }
+}
+
+fn read_end(query: &JointBodies, entity: Entity, anchor: Vec2) -> Option<JointEnd> {
+    let (pos, rot, shape, vel, ang_vel, mass) = query.get(entity).ok()?;
+    let mass = mass.map_or(1.0, |mass| mass.0);
+    let inv_mass = if vel.is_some() && mass > 0.0 { 1.0 / mass } else { 0.0 };
+    let inertia = moment_of_inertia(shape, mass);
+    let inv_inertia = if ang_vel.is_some() && inv_mass > 0.0 && inertia > 0.0 { 1.0 / inertia } else { 0.0 };
+    Some(JointEnd {
+        pos: pos.0,
+        r: Vec2::from_angle(rot.0).rotate(anchor),
+        vel: vel.map_or(Vec2::ZERO, |vel| vel.0),
+        ang_vel: ang_vel.map_or(0.0, |ang_vel| ang_vel.0),
+        inv_mass,
+        inv_inertia,
+    })
+}
+
+fn write_end(query: &mut JointBodies, entity: Entity, end: &JointEnd) {
+    if let Ok((_, _, _, vel, ang_vel, _)) = query.get_mut(entity) {
+        if let Some(mut vel) = vel {
+            vel.0 = end.vel;
+        }
+        if let Some(mut ang_vel) = ang_vel {
+            ang_vel.0 = end.ang_vel;
+        }
+    }
+}
+
+// nudge the velocities of the two ends so that after this tick they still fit the joint
+// we only touch velocities like resolve_body_collisions so the walls still get a say after
+fn solve_joint(kind: JointKind, a: &mut JointEnd, b: &mut JointEnd, time_step: f32, first_pass: bool) {
+    let delta = b.anchor() - a.anchor();
+    let rel_vel = b.anchor_vel() - a.anchor_vel();
+    match kind {
+        JointKind::Revolute => {
+            // the anchors can move apart in any direction so we solve both axes at once
+            let k11 = a.inv_mass + b.inv_mass + a.inv_inertia * a.r.y * a.r.y + b.inv_inertia * b.r.y * b.r.y;
+            let k12 = -a.inv_inertia * a.r.x * a.r.y - b.inv_inertia * b.r.x * b.r.y;
+            let k22 = a.inv_mass + b.inv_mass + a.inv_inertia * a.r.x * a.r.x + b.inv_inertia * b.r.x * b.r.x;
+            let k = Mat2::from_cols(Vec2::new(k11, k12), Vec2::new(k12, k22));
+            if k.determinant().abs() < MU {
+                return;
+            }
+            let impulse = k.inverse() * -(rel_vel + delta * JOINT_CORRECTION / time_step);
+            a.apply_impulse(-impulse);
+            b.apply_impulse(impulse);
+        }
+        JointKind::Distance(length) | JointKind::Rope(length) => {
+            let distance = delta.length();
+            if distance < MU {
+                return;
+            }
+            let dir = delta / distance;
+            let stretch = distance - length;
+            // ropes go slack when they're shorter than their length
+            if matches!(kind, JointKind::Rope(_)) && stretch <= 0.0 {
+                return;
+            }
+            let k = a.inv_mass_along(dir) + b.inv_mass_along(dir);
+            if k < MU {
+                return;
+            }
+            let mut lambda = -(rel_vel.dot(dir) + stretch * JOINT_CORRECTION / time_step) / k;
+            // and can only pull
+            if matches!(kind, JointKind::Rope(_)) {
+                lambda = lambda.min(0.0);
+            }
+            a.apply_impulse(-dir * lambda);
+            b.apply_impulse(dir * lambda);
+        }
+        JointKind::Spring { rest_length, stiffness, damping } => {
+            // a spring is a force not a hard limit so it only gets applied once a tick
+            if !first_pass {
+                return;
+            }
+            let distance = delta.length();
+            if distance < MU {
+                return;
+            }
+            let dir = delta / distance;
+            let force = -(stiffness * (distance - rest_length) + damping * rel_vel.dot(dir));
+            a.apply_impulse(-dir * force * time_step);
+            b.apply_impulse(dir * force * time_step);
+        }
+    }
+}
+
+pub fn solve_joints(
+    config: Res<PhysicsConfig>,
+    joint_query: Query<(Entity, &Joint)>,
+    mut body_query: JointBodies
+) {
+    // each joint changes the velocities the next one sees so they go in a fixed order
+    let mut joints: Vec<(Entity, &Joint)> = joint_query.iter().collect();
+    joints.sort_by_key(|(entity, _)| *entity);
+    for pass in 0..JOINT_ITERATIONS {
+        for &(entity, joint) in joints.iter() {
+            let Some(mut end) = read_end(&body_query, entity, joint.anchor) else {
+                continue;
+            };
+            let mut other_end = match joint.other {
+                Some(other) => match read_end(&body_query, other, joint.other_anchor) {
+                    Some(other_end) => other_end,
+                    None => continue,
+                },
+                // the world never moves
+                None => JointEnd { pos: joint.other_anchor, r: Vec2::ZERO, vel: Vec2::ZERO, ang_vel: 0.0, inv_mass: 0.0, inv_inertia: 0.0 },
+            };
+            solve_joint(joint.kind, &mut other_end, &mut end, config.time_step, pass == 0);
+            write_end(&mut body_query, entity, &end);
+            if let Some(other) = joint.other {
+                write_end(&mut body_query, other, &other_end);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::tests::physics_app;
+
+    // a body that moves and swings but doesn't fall unless we give it gravity
+    fn spawn_body(app: &mut App, pos: Vec2, vel: Vec2) -> Entity {
+        app.world.spawn(BasePhysicsBundle {
+            body: Body { position: Position(pos), shape: Shape::Rect(Vec2::new(0.2, 1.0)), ..default() },
+            velocity: Velocity(vel),
+            ..default()
+        }).id()
+    }
+
+    // no velocity so the joints can't move it
+    fn spawn_post(app: &mut App, pos: Vec2) -> Entity {
+        app.world.spawn(Body { position: Position(pos), shape: Shape::Rect(Vec2::ONE), ..default() }).id()
+    }
+
+    fn anchor_of(app: &App, entity: Entity, anchor: Vec2) -> Vec2 {
+        let pos = app.world.get::<Position>(entity).unwrap().0;
+        let rot = app.world.get::<Rotation>(entity).unwrap().0;
+        pos + Vec2::from_angle(rot).rotate(anchor)
+    }
+
+    fn run_ticks(app: &mut App, ticks: usize, mut check: impl FnMut(&App)) {
+        for _ in 0..ticks {
+            app.world.run_schedule(CoreSchedule::FixedUpdate);
+            check(app);
+        }
+    }
+
+    #[test]
+    fn revolute_joint_keeps_the_anchor_pinned_while_it_swings() {
+        let mut app = physics_app();
+        let pin = Vec2::new(1.0, 2.0);
+        let top = Vec2::new(0.0, 0.5);
+        let body = spawn_body(&mut app, pin - top, Vec2::new(3.0, 0.0));
+        app.world.entity_mut(body).insert((
+            Gravity::default(),
+            Joint::to_world(pin, JointKind::Revolute).with_anchors(top, pin),
+        ));
+
+        run_ticks(&mut app, 300, |app| {
+            let drift = anchor_of(app, body, top).distance(pin);
+            assert!(drift < 0.05, "anchor drifted {drift} off the pin");
+        });
+        // it got swung, not just held still
+        assert!(app.world.get::<Rotation>(body).unwrap().0.abs() > 0.1);
+    }
+
+    #[test]
+    fn distance_joint_keeps_the_bodies_apart_both_ways() {
+        let mut app = physics_app();
+        let post = spawn_post(&mut app, Vec2::ZERO);
+        let body = spawn_body(&mut app, Vec2::new(2.0, 0.0), Vec2::new(-5.0, 0.0));
+        app.world.entity_mut(body).insert(Joint::new(post, JointKind::Distance(2.0)));
+
+        run_ticks(&mut app, 30, |app| {
+            let distance = app.world.get::<Position>(body).unwrap().0.length();
+            assert!((distance - 2.0).abs() < 0.05, "pushed to {distance}");
+        });
+        app.world.get_mut::<Velocity>(body).unwrap().0 = Vec2::new(5.0, 0.0);
+        run_ticks(&mut app, 30, |app| {
+            let distance = app.world.get::<Position>(body).unwrap().0.length();
+            assert!((distance - 2.0).abs() < 0.05, "pulled to {distance}");
+        });
+        assert_eq!(app.world.get::<Position>(post).unwrap().0, Vec2::ZERO);
+    }
+
+    #[test]
+    fn rope_joint_goes_slack_but_stops_at_its_length() {
+        let mut app = physics_app();
+        let post = spawn_post(&mut app, Vec2::ZERO);
+        let body = spawn_body(&mut app, Vec2::new(2.0, 0.0), Vec2::new(-3.0, 0.0));
+        app.world.entity_mut(body).insert(Joint::new(post, JointKind::Rope(2.0)));
+
+        // nothing stops it moving in
+        run_ticks(&mut app, 60, |_| {});
+        let distance = app.world.get::<Position>(body).unwrap().0.length();
+        assert!(distance < 1.5, "rope pushed back to {distance}");
+        assert!(app.world.get::<Velocity>(body).unwrap().0.abs_diff_eq(Vec2::new(-3.0, 0.0), 0.0001));
+
+        // but it can't get further than the rope
+        app.world.get_mut::<Velocity>(body).unwrap().0 = Vec2::new(0.0, 8.0);
+        run_ticks(&mut app, 150, |app| {
+            let distance = app.world.get::<Position>(body).unwrap().0.length();
+            assert!(distance < 2.05, "rope stretched to {distance}");
+        });
+    }
+
+    #[test]
+    fn spring_joint_settles_at_its_rest_length() {
+        let mut app = physics_app();
+        let post = spawn_post(&mut app, Vec2::ZERO);
+        let body = spawn_body(&mut app, Vec2::new(3.0, 0.0), Vec2::ZERO);
+        app.world.entity_mut(body).insert(Joint::new(post, JointKind::Spring { rest_length: 2.0, stiffness: 20.0, damping: 4.0 }));
+
+        // stretched so it gets pulled in first
+        app.world.run_schedule(CoreSchedule::FixedUpdate);
+        assert!(app.world.get::<Velocity>(body).unwrap().0.x < 0.0);
+        // a spring isn't a hard limit, it can overshoot before it settles
+        run_ticks(&mut app, 3000, |_| {});
+        let distance = app.world.get::<Position>(body).unwrap().0.length();
+        assert!((distance - 2.0).abs() < 0.01, "settled at {distance}");
+    }
+}
@@ -0,0 +1,204 @@
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+
+use super::physics::*;
+
+// how far past the hit we look for the contact point, touching shapes don't always
+// count as overlapping with floating point
+const CONTACT_SLOP: f32 = 0.0001;
+
+// what a ray or shape cast ran into
+// the normal is the surface normal of the thing we hit, pointing back out towards us
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct QueryHit {
+    pub entity: Entity,
+    pub distance: f32,
+    pub point: Vec2,
+    pub normal: Vec2,
+}
+
+// what a query is allowed to hit
+// layers works like the filters of CollisionGroups, a collider has to be on one of them
+#[derive(Debug, Clone, Copy)]
+pub struct QueryFilter {
+    pub layers: u32,
+    pub walls: bool,
+    pub exclude: Option<Entity>,
+}
+
+impl Default for QueryFilter {
+    fn default() -> Self {
+        QueryFilter { layers: ALL_LAYERS, walls: true, exclude: None }
+    }
+}
+
+impl QueryFilter {
+    pub fn new(layers: u32, walls: bool) -> QueryFilter {
+        QueryFilter { layers, walls, exclude: None }
+    }
+
+    // usually the entity doing the asking so it doesn't hit itself
+    pub fn excluding(self, entity: Entity) -> QueryFilter {
+        QueryFilter { exclude: Some(entity), ..self }
+    }
+}
+
+type QueryColliders<'w, 's> = Query<'w, 's,
+    (Entity, &'static GlobalPosition, &'static GlobalRotation, &'static Shape, Option<&'static CollisionGroups>),
+    (With<Collider>, Without<Wall>)>;
+
+// ask the physics world questions like "whats below me" or "can this enemy see the player"
+// looks at every Wall and every Collider with a Shape
+#[derive(SystemParam)]
+pub struct PhysicsQuery<'w, 's> {
+    walls: Query<'w, 's, (Entity, &'static Position, &'static Shape, Option<&'static Rotation>), With<Wall>>,
+    colliders: QueryColliders<'w, 's>,
+}
+
+impl<'w, 's> PhysicsQuery<'w, 's> {
+    // every shape the filter lets through as (entity, position, shape, angle)
+    fn shapes(&self, filter: QueryFilter) -> impl Iterator<Item = (Entity, Vec2, &Shape, f32)> + '_ {
+        let walls = self.walls.iter()
+            .filter(move |_| filter.walls)
+            .map(|(entity, pos, shape, rot)| (entity, pos.0, shape, rot.map_or(0.0, |rot| rot.0)));
+        let colliders = self.colliders.iter()
+            .filter(move |(.., groups)| groups.copied().unwrap_or_default().memberships & filter.layers != 0)
+            .map(|(entity, pos, rot, shape, _)| (entity, pos.0, shape, rot.0));
+        walls.chain(colliders).filter(move |(entity, ..)| Some(*entity) != filter.exclude)
+    }
+
+    // the first thing a ray from origin going dir hits within max_distance
+    pub fn cast_ray(&self, origin: Vec2, dir: Vec2, max_distance: f32, filter: QueryFilter) -> Option<QueryHit> {
+        let motion = dir.normalize_or_zero() * max_distance;
+        self.shapes(filter)
+            .filter_map(|(entity, pos, shape, angle)| {
+                ray_shape_cast(&origin, &motion, &pos, shape, angle).map(|(time, normal)| QueryHit {
+                    entity,
+                    distance: time * max_distance,
+                    point: origin + motion * time,
+                    normal,
+                })
+            })
+            .min_by(|a, b| a.distance.total_cmp(&b.distance))
+    }
+
+    // the first thing the shape at pos hits moving dir for max_distance
+    pub fn cast_shape(
+        &self, shape: &Shape, pos: Vec2, angle: f32, 
+        dir: Vec2, max_distance: f32, filter: QueryFilter
+    ) -> Option<QueryHit> {
+        let dir = dir.normalize_or_zero();
+        let motion = dir * max_distance;
+        self.shapes(filter)
+            .filter_map(|(entity, other_pos, other_shape, other_angle)| {
+                let hit = shapes_casted_collision(&pos, shape, angle, &other_pos, other_shape, other_angle, &motion)?;
+                let distance = hit.time * max_distance;
+                let point = detect_collision_pair(
+                        &(pos + dir * (distance + CONTACT_SLOP)), shape, angle, &other_pos, other_shape, other_angle)
+                    .map_or(pos + dir * distance, |contact| contact.points[0]);
+                Some(QueryHit { entity, distance, point, normal: -hit.normal })
+            })
+            .min_by(|a, b| a.distance.total_cmp(&b.distance))
+    }
+
+    // everything the point is inside of
+    pub fn overlap_point(&self, point: Vec2, filter: QueryFilter) -> Vec<Entity> {
+        self.shapes(filter)
+            .filter(|(_, pos, shape, angle)| point_in_shape(&point, pos, shape, *angle))
+            .map(|(entity, ..)| entity)
+            .collect()
+    }
+
+    // everything the shape at pos is touching
+    pub fn overlap_shape(&self, shape: &Shape, pos: Vec2, angle: f32, filter: QueryFilter) -> Vec<Entity> {
+        self.shapes(filter)
+            .filter(|(_, other_pos, other_shape, other_angle)| 
+                detect_collision_pair(&pos, shape, angle, other_pos, other_shape, *other_angle).is_some())
+            .map(|(entity, ..)| entity)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::SystemState;
+
+    use super::*;
+
+    // a 4 wide wall with its top at y 0 and an enemy sized circle floating above it at y 1
+    fn query_world() -> (World, Entity, Entity) {
+        let mut world = World::new();
+        let wall = world.spawn((Wall, Position(Vec2::new(0.0, -0.5)), Shape::Rect(Vec2::new(4.0, 1.0)))).id();
+        let enemy = world.spawn((
+            Body { global_position: GlobalPosition(Vec2::new(0.0, 1.0)), shape: Shape::Circle(0.25), ..default() },
+            Collider,
+            CollisionGroups::new(ENEMY_LAYER, ALL_LAYERS),
+        )).id();
+        (world, wall, enemy)
+    }
+
+    #[test]
+    fn cast_ray_hits_the_closest_thing_the_filter_lets_through() {
+        let (mut world, wall, enemy) = query_world();
+        let mut state: SystemState<PhysicsQuery> = SystemState::new(&mut world);
+        let query = state.get(&world);
+
+        let hit = query.cast_ray(Vec2::new(0.0, 2.0), Vec2::NEG_Y, 5.0, QueryFilter::default()).unwrap();
+        assert_eq!(hit.entity, enemy);
+        assert!((hit.distance - 0.75).abs() < 0.001);
+        assert!(hit.point.abs_diff_eq(Vec2::new(0.0, 1.25), 0.001));
+        assert!(hit.normal.abs_diff_eq(Vec2::Y, 0.001));
+
+        // the enemy isn't on the player layer so we go right through it to the wall
+        let hit = query.cast_ray(Vec2::new(0.0, 2.0), Vec2::NEG_Y, 5.0, QueryFilter::new(PLAYER_LAYER, true)).unwrap();
+        assert_eq!(hit.entity, wall);
+        assert!((hit.distance - 2.0).abs() < 0.001);
+        assert!(hit.point.abs_diff_eq(Vec2::ZERO, 0.001));
+        assert!(hit.normal.abs_diff_eq(Vec2::Y, 0.001));
+
+        assert!(query.cast_ray(Vec2::new(0.0, 2.0), Vec2::NEG_Y, 5.0, QueryFilter::new(PLAYER_LAYER, false)).is_none());
+        assert!(query.cast_ray(Vec2::new(0.0, 2.0), Vec2::NEG_Y, 1.0, QueryFilter::new(PLAYER_LAYER, true)).is_none());
+        let hit = query.cast_ray(Vec2::new(0.0, 2.0), Vec2::NEG_Y, 5.0, QueryFilter::default().excluding(enemy)).unwrap();
+        assert_eq!(hit.entity, wall);
+    }
+
+    #[test]
+    fn cast_shape_stops_where_the_shape_first_touches() {
+        let (mut world, wall, enemy) = query_world();
+        let mut state: SystemState<PhysicsQuery> = SystemState::new(&mut world);
+        let query = state.get(&world);
+        let shape = Shape::Rect(Vec2::ONE);
+
+        // from off to the side so we miss the enemy
+        let hit = query.cast_shape(&shape, Vec2::new(1.5, 2.0), 0.0, Vec2::NEG_Y, 5.0, QueryFilter::default()).unwrap();
+        assert_eq!(hit.entity, wall);
+        assert!((hit.distance - 1.5).abs() < 0.001);
+        assert!((hit.point.y).abs() < 0.001);
+        assert!(hit.normal.abs_diff_eq(Vec2::Y, 0.001));
+
+        let hit = query.cast_shape(&shape, Vec2::new(0.0, 3.0), 0.0, Vec2::NEG_Y, 5.0, QueryFilter::default()).unwrap();
+        assert_eq!(hit.entity, enemy);
+        assert!((hit.distance - 1.25).abs() < 0.001);
+
+        let hit = query.cast_shape(&shape, Vec2::new(0.0, 3.0), 0.0, Vec2::NEG_Y, 5.0, QueryFilter::new(PLAYER_LAYER, true)).unwrap();
+        assert_eq!(hit.entity, wall);
+    }
+
+    #[test]
+    fn overlaps_find_everything_touching() {
+        let (mut world, wall, enemy) = query_world();
+        let mut state: SystemState<PhysicsQuery> = SystemState::new(&mut world);
+        let query = state.get(&world);
+
+        assert_eq!(query.overlap_point(Vec2::new(1.0, -0.5), QueryFilter::default()), vec![wall]);
+        assert_eq!(query.overlap_point(Vec2::new(0.1, 1.1), QueryFilter::default()), vec![enemy]);
+        assert!(query.overlap_point(Vec2::new(0.1, 1.1), QueryFilter::new(PLAYER_LAYER, true)).is_empty());
+        assert!(query.overlap_point(Vec2::new(1.0, 2.0), QueryFilter::default()).is_empty());
+
+        let mut touching = query.overlap_shape(&Shape::Rect(Vec2::new(1.0, 2.0)), Vec2::new(0.0, 0.5), 0.0, QueryFilter::default());
+        touching.sort();
+        assert_eq!(touching, vec![wall, enemy]);
+        assert_eq!(query.overlap_shape(&Shape::Rect(Vec2::new(1.0, 2.0)), Vec2::new(0.0, 0.5), 0.0,
+            QueryFilter::new(PLAYER_LAYER, true)), vec![wall]);
+    }
+}
@@ -2,14 +2,14 @@ use std::time::Duration;
 
 use bevy::{prelude::*, transform::commands};
 
-use super::{physics::*, weapon::*, walls::{WallCollider, WallSensor}};
+use super::{physics::*, physics_query::*, weapon::*};
 
 #[derive(Component, Default)]
 pub struct Player;
 #[derive(Component, Default)]
 pub struct Health(f32);
 
-#[derive(Default)]
+#[derive(Default, Clone)]
 pub enum JumpStates {
     Jumping(Timer),
     Jumpable,
@@ -17,7 +17,7 @@ pub enum JumpStates {
     Unjumpable
 }
 
-#[derive(Component, Default)]
+#[derive(Component, Default, Clone)]
 pub struct Jumper {
     // TODO replace this with an enium for cleaner modeling?
     state: JumpStates
@@ -28,15 +28,15 @@ pub struct DoubleJumper {
     state: JumpStates
 }
 
-pub fn tick_jump_times(mut commands: Commands, mut query: Query<&mut Jumper>) {
+pub fn tick_jump_times(mut commands: Commands, config: Res<PhysicsConfig>, mut query: Query<&mut Jumper>) {
     for mut jumper in query.iter_mut() {
         if let JumpStates::Jumping(ref mut timer) = jumper.state {
-            timer.tick(Duration::from_secs_f32(PHYSICS_TIME_STEP));
+            timer.tick(Duration::from_secs_f32(config.time_step));
         }
     }
 }
 
-#[derive(Default)]
+#[derive(Default, Clone)]
 pub enum AttackStates {
     Attacking(Timer, Entity),
     #[default]
@@ -44,19 +44,39 @@ pub enum AttackStates {
     NoAttack(Timer)
 }
 
-#[derive(Component, Default)]
+#[derive(Component, Default, Clone)]
 pub struct Attacker {
     state: AttackStates
 }
 
-pub fn tick_attack_times(mut commands: Commands, mut query: Query<&mut Attacker>) {
+impl Attacker {
+    pub fn start_swing(&mut self, hammer: Entity) {
+        self.state = AttackStates::Attacking(Timer::from_seconds(HAMMER_SWING_TIME, TimerMode::Once), hammer);
+    }
+
+    // put the hammer away and wait a bit before we can swing again
+    pub fn end_swing(&mut self) {
+        self.state = AttackStates::NoAttack(Timer::from_seconds(HAMMER_SWING_TIME, TimerMode::Once));
+    }
+
+    // the hammer shaft we're swinging, if we are, only the snapshots need to know
+    #[cfg(debug_assertions)]
+    pub fn hammer(&self) -> Option<Entity> {
+        match self.state {
+            AttackStates::Attacking(_, hammer) => Some(hammer),
+            _ => None,
+        }
+    }
+}
+
+pub fn tick_attack_times(mut commands: Commands, config: Res<PhysicsConfig>, mut query: Query<&mut Attacker>) {
     for mut attacker in query.iter_mut() {
         match attacker.state {
             AttackStates::Attacking(ref mut timer, _) => {
-                timer.tick(Duration::from_secs_f32(PHYSICS_TIME_STEP));
+                timer.tick(Duration::from_secs_f32(config.time_step));
             }
             AttackStates::NoAttack(ref mut timer) => {
-                timer.tick(Duration::from_secs_f32(PHYSICS_TIME_STEP));
+                timer.tick(Duration::from_secs_f32(config.time_step));
             }
             _ => (),
         }
@@ -72,6 +92,11 @@ const PLAYER_RUN_ACCEL: Vec2 = Vec2 {x:30.0, y:0.0};
 const PLAYER_JUMP_ACCEL: Vec2 = Vec2 {x:0.0, y:100.0};
 const PLAYER_JUMP_VEL:Vec2 = Vec2 {x: 0.0, y: 8.0};
 const PLAYER_JUMP_TIME:f32 = 0.3;
+// how far under we have to be before we can swim up, jumping again like we're on the ground
+const PLAYER_SWIM_DEPTH: f32 = 0.5;
+// how far above the ground we can be and still jump, so little bumps and the gap we keep
+// off of walls don't eat jumps
+const PLAYER_GROUND_CHECK_DISTANCE: f32 = 0.05;
 
 #[derive(Bundle, Default)]
 pub struct PlayerBundle {
@@ -81,11 +106,15 @@ pub struct PlayerBundle {
     pub gravity: Gravity,
     pub wall_collider: WallCollider,
     pub wall_sensor: WallSensor,
+    pub slope_walker: SlopeWalker,
+    pub submerged: Submerged,
+    pub active_range_anchor: ActiveRangeAnchor,
     pub physics_controller: PhysicsControllerBundle,
     pub jumper: Jumper,
     pub attacker: Attacker,
     pub health: Health,
-    pub collider: Collider
+    pub collider: Collider,
+    pub collision_groups: CollisionGroups
 }
 
 impl PlayerBundle {
@@ -114,19 +143,34 @@ impl PlayerBundle {
                 },
                 ..default()
             },
+            collision_groups: CollisionGroups::new(PLAYER_LAYER, 
+                ENEMY_LAYER | ENEMY_WEAPON_LAYER | PICKUP_LAYER | TRIGGER_LAYER),
             ..default()
         }
     }
 }
 
+type PlayerControls<'w, 's> = Query<'w, 's,
+    (Entity, &'static WallSensor, &'static Submerged, &'static mut Jumper, &'static mut AdjustAcceleration,
+        &'static mut OverrideVelocity, &'static mut Attacker),
+    With<Player>>;
+
 // shouldk i split this up more?
 // like a jump and then side movement and stuff hmmmm
 pub fn move_player(
     mut commands: Commands,
     keyboard_input: Res<Input<KeyCode>>,
-    mut query: Query<(Entity, &WallSensor, &mut Jumper, &mut AdjustAcceleration, &mut OverrideVelocity, &mut Attacker), With<Player>>
+    physics_query: PhysicsQuery,
+    body_query: Query<(&Position, &Shape), With<Player>>,
+    mut query: PlayerControls
 ) {
-    let (player, wall_sensor, mut jumper, mut adjust_accel, mut over_vel, mut attacker) = query.single_mut();
+    let (player, wall_sensor, submerged, mut jumper, mut adjust_accel, mut over_vel, mut attacker) = query.single_mut();
+    let swimming = submerged.fraction >= PLAYER_SWIM_DEPTH;
+    // whats below us, any wall we could stand on that's close enough counts
+    let (pos, shape) = body_query.single();
+    let on_ground = wall_sensor.down || physics_query
+        .cast_shape(shape, pos.0, 0.0, Vec2::NEG_Y, PLAYER_GROUND_CHECK_DISTANCE, QueryFilter::new(0, true).excluding(player))
+        .is_some_and(|hit| hit.normal.y >= DEFAULT_MAX_WALKABLE_ANGLE.cos());
     if keyboard_input.just_pressed(KeyCode::Left) {
         adjust_accel.0 -= PLAYER_RUN_ACCEL;
     } else if keyboard_input.just_released(KeyCode::Left) {
@@ -145,7 +189,7 @@ pub fn move_player(
                 over_vel.1 = Some(PLAYER_JUMP_VEL.y);
                 jumper.state = JumpStates::Jumping(Timer::from_seconds(PLAYER_JUMP_TIME, TimerMode::Once))
                 //adjust_accel.0 += PLAYER_JUMP_ACCEL;
-            } else if !wall_sensor.down {
+            } else if !on_ground && !swimming {
                 jumper.state = JumpStates::Unjumpable
             }  
         }
@@ -157,13 +201,15 @@ pub fn move_player(
             }
         }
         JumpStates::Unjumpable => {
-            if wall_sensor.down {
+            if on_ground || swimming {
                 jumper.state = JumpStates::Jumpable;
             }
         }
     }
 
-    if keyboard_input.pressed(KeyCode::Down) {
+    // drop through any one way platform we are standing on
+    if keyboard_input.just_pressed(KeyCode::Down) && wall_sensor.down {
+        commands.entity(player).insert(DropThrough::default());
     }
 
     match &attacker.state {
@@ -176,13 +222,13 @@ pub fn move_player(
                 let head = commands.spawn(hammer.1).id();
                 commands.entity(player).push_children(&[shaft]);
                 commands.entity(shaft).push_children(&[head]);
-                attacker.state = AttackStates::Attacking(Timer::from_seconds(HAMMER_SWING_TIME, TimerMode::Once), shaft);
+                attacker.start_swing(shaft);
              }
         }
         AttackStates::Attacking(timer, entity) => {
             if timer.finished() {
                 commands.entity(*entity).despawn_recursive();
-                attacker.state = AttackStates::NoAttack(Timer::from_seconds(HAMMER_SWING_TIME, TimerMode::Once));
+                attacker.end_swing();
             }
         }
         AttackStates::NoAttack(timer) => {
@@ -194,6 +240,25 @@ pub fn move_player(
 }
 
 
+// put a trigger on this and falling into it puts the player back at respawn
+#[derive(Component, Default)]
+pub struct KillZone {
+    pub respawn: Vec2,
+}
+
+pub fn respawn_player(
+    mut entered: EventReader<TriggerEntered>,
+    kill_query: Query<&KillZone>,
+    mut query: Query<(&mut Position, &mut Velocity), With<Player>>
+) {
+    for event in entered.iter() {
+        if let (Ok(kill_zone), Ok((mut pos, mut vel))) = (kill_query.get(event.trigger), query.get_mut(event.entity)) {
+            pos.0 = kill_zone.respawn;
+            vel.0 = Vec2::ZERO;
+        }
+    }
+}
+
 #[cfg(debug_assertions)]
 pub fn debug_player(query: Query<(&GlobalTransform, &Visibility, &Position, &Velocity, &Acceleration), With<Player>>) {
     let (global, vis, pos, vel, accel) = query.get_single().unwrap();
@@ -0,0 +1,225 @@
+use bevy::hierarchy::despawn_with_children_recursive;
+use bevy::prelude::*;
+use bevy::utils::HashSet;
+
+use super::physics::*;
+use super::player::{Jumper, Attacker};
+use super::walls::PlatformPath;
+use super::weapon::HammerTimer;
+
+// every entity that had the component when we took the snapshot and what it was
+type Saved<T> = Vec<(Entity, T)>;
+
+fn save<T: Component + Clone>(world: &mut World) -> Saved<T> {
+    let mut query = world.query::<(Entity, &T)>();
+    query.iter(world).map(|(entity, component)| (entity, component.clone())).collect()
+}
+
+// puts the saved components back and takes the component off anything in the snapshot that didn't
+// have it back then, so a DropThrough added after the snapshot goes away again
+fn load<T: Component + Clone>(world: &mut World, bodies: &HashSet<Entity>, saved: &Saved<T>) {
+    let mut query = world.query_filtered::<Entity, With<T>>();
+    let current: Vec<Entity> = query.iter(world).collect();
+    for entity in current {
+        if bodies.contains(&entity) && !saved.iter().any(|(saved_entity, _)| *saved_entity == entity) {
+            world.entity_mut(entity).remove::<T>();
+        }
+    }
+    for (entity, component) in saved.iter() {
+        if let Some(mut entity) = world.get_entity_mut(*entity) {
+            entity.insert(component.clone());
+        }
+    }
+}
+
+// the whole physics state in one value, take one with capture and put it back with restore
+// for rollback, rewinding and restarting
+// it only covers entities that are still around, anything despawned since the snapshot stays
+// gone and any body (anything with a Position) spawned since gets despawned, like a hammer swung after it
+#[derive(Default, Clone)]
+pub struct PhysicsSnapshot {
+    positions: Saved<Position>,
+    rotations: Saved<Rotation>,
+    velocities: Saved<Velocity>,
+    accelerations: Saved<Acceleration>,
+    angular_velocities: Saved<AngularVelocity>,
+    // these get worked out at the end of a tick but the next tick starts from them
+    global_positions: Saved<GlobalPosition>,
+    global_rotations: Saved<GlobalRotation>,
+    transforms: Saved<TwoDimTrans>,
+    previous_positions: Saved<PreviousPosition>,
+    previous_rotations: Saved<PreviousRotation>,
+    ccds: Saved<Ccd>,
+    // controllers
+    override_velocities: Saved<OverrideVelocity>,
+    override_accelerations: Saved<OverrideAcceleration>,
+    adjust_velocities: Saved<AdjustVelocity>,
+    adjust_accelerations: Saved<AdjustAcceleration>,
+    override_angular_velocities: Saved<OverrideAngularVelocity>,
+    // timers and sensors
+    jumpers: Saved<Jumper>,
+    attackers: Saved<Attacker>,
+    hammer_timers: Saved<HammerTimer>,
+    drop_throughs: Saved<DropThrough>,
+    wall_sensors: Saved<WallSensor>,
+    submerged: Saved<Submerged>,
+    platform_paths: Saved<PlatformPath>,
+    can_sleep: Saved<CanSleep>,
+    sleeping: Saved<Sleeping>,
+    // so the collision events pick up where they left off
+    colliding: CollidingPairs,
+    checksum: PhysicsChecksum,
+}
+
+impl PhysicsSnapshot {
+    pub fn capture(world: &mut World) -> PhysicsSnapshot {
+        PhysicsSnapshot {
+            positions: save(world),
+            rotations: save(world),
+            velocities: save(world),
+            accelerations: save(world),
+            angular_velocities: save(world),
+            global_positions: save(world),
+            global_rotations: save(world),
+            transforms: save(world),
+            previous_positions: save(world),
+            previous_rotations: save(world),
+            ccds: save(world),
+            override_velocities: save(world),
+            override_accelerations: save(world),
+            adjust_velocities: save(world),
+            adjust_accelerations: save(world),
+            override_angular_velocities: save(world),
+            jumpers: save(world),
+            attackers: save(world),
+            hammer_timers: save(world),
+            drop_throughs: save(world),
+            wall_sensors: save(world),
+            submerged: save(world),
+            platform_paths: save(world),
+            can_sleep: save(world),
+            sleeping: save(world),
+            colliding: world.get_resource::<CollidingPairs>().cloned().unwrap_or_default(),
+            checksum: world.get_resource::<PhysicsChecksum>().copied().unwrap_or_default(),
+        }
+    }
+
+    pub fn restore(&self, world: &mut World) {
+        let bodies: HashSet<Entity> = self.positions.iter().map(|(entity, _)| *entity).collect();
+        let mut query = world.query_filtered::<Entity, With<Position>>();
+        let spawned: Vec<Entity> = query.iter(world).filter(|entity| !bodies.contains(entity)).collect();
+        for entity in spawned {
+            // children go with their parents so they might already be gone
+            if world.get_entity(entity).is_some() {
+                despawn_with_children_recursive(world, entity);
+            }
+        }
+
+        load(world, &bodies, &self.positions);
+        load(world, &bodies, &self.rotations);
+        load(world, &bodies, &self.velocities);
+        load(world, &bodies, &self.accelerations);
+        load(world, &bodies, &self.angular_velocities);
+        load(world, &bodies, &self.global_positions);
+        load(world, &bodies, &self.global_rotations);
+        load(world, &bodies, &self.transforms);
+        load(world, &bodies, &self.previous_positions);
+        load(world, &bodies, &self.previous_rotations);
+        load(world, &bodies, &self.ccds);
+        load(world, &bodies, &self.override_velocities);
+        load(world, &bodies, &self.override_accelerations);
+        load(world, &bodies, &self.adjust_velocities);
+        load(world, &bodies, &self.adjust_accelerations);
+        load(world, &bodies, &self.override_angular_velocities);
+        load(world, &bodies, &self.jumpers);
+        load(world, &bodies, &self.attackers);
+        load(world, &bodies, &self.hammer_timers);
+        load(world, &bodies, &self.drop_throughs);
+        load(world, &bodies, &self.wall_sensors);
+        load(world, &bodies, &self.submerged);
+        load(world, &bodies, &self.platform_paths);
+        load(world, &bodies, &self.can_sleep);
+        load(world, &bodies, &self.sleeping);
+        // a hammer we were swinging back then might have been put away since, it doesn't come back
+        let mut query = world.query::<(Entity, &Attacker)>();
+        let lost_hammers: Vec<Entity> = query.iter(world)
+            .filter(|(_, attacker)| attacker.hammer().is_some_and(|hammer| world.get_entity(hammer).is_none()))
+            .map(|(entity, _)| entity)
+            .collect();
+        for entity in lost_hammers {
+            world.get_mut::<Attacker>(entity).unwrap().end_swing();
+        }
+        world.insert_resource(self.colliding.clone());
+        world.insert_resource(self.checksum);
+    }
+}
+
+// F5 saves the physics and F9 rewinds back to it
+#[cfg(debug_assertions)]
+pub fn debug_snapshot(world: &mut World, mut saved: Local<Option<PhysicsSnapshot>>) {
+    let (save_pressed, load_pressed) = {
+        let keyboard_input = world.resource::<Input<KeyCode>>();
+        (keyboard_input.just_pressed(KeyCode::F5), keyboard_input.just_pressed(KeyCode::F9))
+    };
+    if save_pressed {
+        *saved = Some(PhysicsSnapshot::capture(world));
+    } else if load_pressed {
+        if let Some(snapshot) = saved.as_ref() {
+            snapshot.restore(world);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn restore_puts_bodies_back_and_gets_rid_of_anything_newer() {
+        let mut world = World::new();
+        let body = world.spawn((Position(Vec2::ZERO), Velocity(Vec2::X), Attacker::default())).id();
+        let hammer = world.spawn(Position(Vec2::Y)).id();
+        world.entity_mut(body).push_children(&[hammer]);
+        world.get_mut::<Attacker>(body).unwrap().start_swing(hammer);
+        let snapshot = PhysicsSnapshot::capture(&mut world);
+
+        // the swing ends and a new one starts, and the body moves and drops through a platform
+        despawn_with_children_recursive(&mut world, hammer);
+        let new_hammer = world.spawn(Position(Vec2::Y)).id();
+        let new_head = world.spawn(Position(Vec2::Y)).id();
+        world.entity_mut(new_hammer).push_children(&[new_head]);
+        world.entity_mut(body).push_children(&[new_hammer]);
+        world.get_mut::<Attacker>(body).unwrap().start_swing(new_hammer);
+        world.get_mut::<Position>(body).unwrap().0 = Vec2::new(3.0, 0.0);
+        world.get_mut::<Velocity>(body).unwrap().0 = Vec2::NEG_X;
+        world.entity_mut(body).insert(DropThrough::default());
+        let other = world.spawn(Velocity(Vec2::X)).id();
+
+        snapshot.restore(&mut world);
+
+        assert_eq!(world.get::<Position>(body).unwrap().0, Vec2::ZERO);
+        assert_eq!(world.get::<Velocity>(body).unwrap().0, Vec2::X);
+        assert!(world.get::<DropThrough>(body).is_none());
+        assert!(world.get_entity(new_hammer).is_none());
+        assert!(world.get_entity(new_head).is_none());
+        assert!(world.get::<Children>(body).is_none_or(|children| children.is_empty()));
+        // the old hammer is gone for good so we aren't swinging it anymore
+        assert_eq!(world.get::<Attacker>(body).unwrap().hammer(), None);
+        // not a body, so the snapshot leaves it alone
+        assert_eq!(world.get::<Velocity>(other).unwrap().0, Vec2::X);
+    }
+
+    #[test]
+    fn restore_keeps_swinging_a_hammer_that_is_still_around() {
+        let mut world = World::new();
+        let body = world.spawn((Position(Vec2::ZERO), Attacker::default())).id();
+        let hammer = world.spawn(Position(Vec2::Y)).id();
+        world.get_mut::<Attacker>(body).unwrap().start_swing(hammer);
+        let snapshot = PhysicsSnapshot::capture(&mut world);
+        world.get_mut::<Attacker>(body).unwrap().end_swing();
+
+        snapshot.restore(&mut world);
+
+        assert_eq!(world.get::<Attacker>(body).unwrap().hammer(), Some(hammer));
+    }
+}
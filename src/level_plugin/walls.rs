@@ -1,22 +1,12 @@
 use bevy::prelude::*;
+use bevy::render::mesh::{Indices, PrimitiveTopology};
+use bevy::sprite::MaterialMesh2dBundle;
+use bevy::utils::Duration;
 use super::physics::*;
 
-#[derive(Component, Default)]
-pub struct Wall;
-
-#[derive(Component, Default)]
-pub struct WallCollider;
-
 #[derive(Component, Default)]
 pub struct WallDectector;
 
-#[derive(Component, Default)]
-pub struct WallSensor {
-    pub left: bool,
-    pub right: bool,
-    pub down: bool
-}
-
 #[derive(Component, Default)]
 pub struct GroundSensor(bool);
 
@@ -27,9 +17,143 @@ pub struct WallBundle {
     pub wall: Wall,
     pub sprite_bundle: SpriteBundle,
     pub position: Position,
+    pub rotation: Rotation,
+    pub shape: Shape,
+}
+
+// a wall in any convex shape, mostly for slopes and ramps
+// the points are relative to pos, sprites can only be rects so these get drawn with a mesh
+#[derive(Bundle, Default)]
+pub struct PolyWallBundle {
+    pub wall: Wall,
+    pub mesh_bundle: MaterialMesh2dBundle<ColorMaterial>,
+    pub position: Position,
+    pub rotation: Rotation,
     pub shape: Shape,
 }
 
+impl PolyWallBundle {
+    pub fn new(
+        pos: Vec2, points: Vec<Vec2>,
+        meshes: &mut Assets<Mesh>, materials: &mut Assets<ColorMaterial>
+    ) -> PolyWallBundle {
+        let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+        let positions: Vec<[f32; 3]> = points.iter().map(|point| [point.x, point.y, 0.0]).collect();
+        // the points are convex so we can fan out triangles from the first one
+        // with less than 3 points there's nothing to draw and the shape never hits anything
+        let indices: Vec<u32> = (1..(points.len() as u32).saturating_sub(1)).flat_map(|i| [0, i, i + 1]).collect();
+        mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+        mesh.set_indices(Some(Indices::U32(indices)));
+        PolyWallBundle {
+            mesh_bundle: MaterialMesh2dBundle {
+                mesh: meshes.add(mesh).into(),
+                material: materials.add(ColorMaterial::from(WALL_COLOR)),
+                ..default()
+            },
+            shape: Shape::Poly(points),
+            position: Position(pos),
+            ..default()
+        }
+    }
+}
+
+// a wall that moves back and forth through waypoints at a set speed
+// anything standing on it gets carried along
+#[derive(Component, Default, Clone)]
+pub struct PlatformPath {
+    pub waypoints: Vec<Vec2>,
+    pub speed: f32,
+    pub next: usize,
+}
+
+#[derive(Bundle, Default)]
+pub struct MovingPlatformBundle {
+    pub wall_bundle: WallBundle,
+    pub velocity: Velocity,
+    pub path: PlatformPath,
+    pub interpolation: InterpolationBundle,
+}
+
+impl MovingPlatformBundle {
+    // starts at start and goes through the rest of the waypoints and back around to start
+    // with no other waypoints it just sits at start
+    pub fn new(start: Vec2, waypoints: Vec<Vec2>, size: Vec2, speed: f32) -> MovingPlatformBundle {
+        MovingPlatformBundle {
+            wall_bundle: WallBundle::new(start, size),
+            path: PlatformPath { waypoints: std::iter::once(start).chain(waypoints).collect(), speed, next: 0 },
+            ..default()
+        }
+    }
+}
+
+#[derive(Bundle, Default)]
+pub struct OneWayPlatformBundle {
+    pub wall_bundle: WallBundle,
+    pub one_way: OneWayPlatform,
+}
+
+impl OneWayPlatformBundle {
+    pub fn new(pos: Vec2, size: Vec2) -> OneWayPlatformBundle {
+        OneWayPlatformBundle {
+            wall_bundle: WallBundle::new(pos, size),
+            ..default()
+        }
+    }
+}
+
+const CHAIN_COLOR: Color = Color::rgb(0.5, 0.4, 0.3);
+
+// a block hanging off a joint, string a few together for a chain that swings when you bump it
+#[derive(Bundle)]
+pub struct ChainLinkBundle {
+    pub sprite: SpriteBundle,
+    pub physics: BasePhysicsBundle,
+    pub gravity: Gravity,
+    pub joint: Joint,
+}
+
+impl ChainLinkBundle {
+    pub fn new(pos: Vec2, size: Vec2, joint: Joint) -> ChainLinkBundle {
+        ChainLinkBundle {
+            sprite: SpriteBundle {
+                transform: Transform {
+                    translation: pos.extend(0.0),
+                    ..default()
+                },
+                sprite: Sprite {
+                    color: CHAIN_COLOR,
+                    custom_size: Some(size),
+                    ..default()
+                },
+                ..default()
+            },
+            physics: BasePhysicsBundle {
+                body: Body {
+                    position: Position(pos),
+                    shape: Shape::Rect(size),
+                    ..default()
+                },
+                ..default()
+            },
+            gravity: Gravity::default(),
+            joint,
+        }
+    }
+}
+
+pub fn tick_drop_through(
+    mut commands: Commands, 
+    config: Res<PhysicsConfig>, 
+    mut query: Query<(Entity, &mut DropThrough)>
+) {
+    for (entity, mut drop) in query.iter_mut() {
+        drop.0.tick(Duration::from_secs_f32(config.time_step));
+        if drop.0.finished() {
+            commands.entity(entity).remove::<DropThrough>();
+        }
+    }
+}
+
 impl WallBundle {
     pub fn new(pos: Vec2, size: Vec2) -> WallBundle {
         WallBundle {
@@ -50,63 +174,35 @@ impl WallBundle {
 
 
 
-// TODO Theorically you could move this into the physics system as a solid_immovable object or something
-// but I dont' knwo if that lvel of abstraction is necessary.
-pub fn handle_wall_collisions(
-    wall_query: Query<(&Position, &Shape), (With<Wall>, Without<WallCollider>)>,
-    mut wall_collider_query: Query<(&mut Position, &Shape, &mut Velocity, Option<&mut WallSensor>), 
-        (With<WallCollider>, Without<Wall>)>
-) {
-    eprintln!("cast");
-    let zero_velocity = Velocity(Vec2::ZERO);
-    for (mut col_pos, col_shape, mut col_vel, mut wall_sensor) in wall_collider_query.iter_mut() {
-        if let Some(sensor) = wall_sensor.as_mut() {
-            sensor.left = false;
-            sensor.right = false;
-            sensor.down = false;
+// point the platforms at their next waypoint, we set the velocity and let apply_velocity move
+// them so the wall casting knows how fast they're going
+pub fn follow_platform_paths(config: Res<PhysicsConfig>, mut query: Query<(&Position, &mut Velocity, &mut PlatformPath)>) {
+    for (pos, mut vel, mut path) in query.iter_mut() {
+        if path.waypoints.is_empty() {
+            vel.0 = Vec2::ZERO;
+            continue;
         }
-        for (wall_pos, wall_shape) in wall_query.iter() {
-            match (&col_shape, &wall_shape) {
-                (Shape::Rect(col_size), Shape::Rect(wall_size)) => {
-                    // Calculat the upper left position for easier fucntion calcs
-                    let col_upper_left = Vec2 {x: col_pos.0.x - col_size.x*0.5, y: col_pos.0.y + col_size.y*0.5};
-                    let wall_upper_left = Vec2 {x: wall_pos.0.x - wall_size.x*0.5, y: wall_pos.0.y + wall_size.y*0.5};
-                    let inter_angle = rectangles_casted_collision(
-                        &col_upper_left, &col_size, &col_vel.0, 
-                        &wall_upper_left, &wall_size, &zero_velocity.0);
-                    // If the platform is moving then the position setting won't work
-                    // TODO handle th cast casting better
-                    if let Some(angle) = inter_angle {
-                        let offset = (*col_size + *wall_size) * 0.5;
-                        if (3.0*std::f32::consts::PI / 2.0) - MU <= angle {    
-                            col_pos.0.y = wall_pos.0.y + offset.y +  MU;
-                            if let Some(sensor) = wall_sensor.as_mut() {
-                                sensor.down = true;
-                            }
-                        } else if std::f32::consts::PI - MU <= angle {
-                            col_pos.0.x = wall_pos.0.x + offset.x + MU;
-                            if let Some(sensor) = wall_sensor.as_mut() {
-                                sensor.left = true;
-                            }
-                        } else if (std::f32::consts::PI / 2.0) - MU <= angle {
-                            col_pos.0.y = wall_pos.0.y - offset.y - MU;
-                        } else {
-                            col_pos.0.x = wall_pos.0.x - offset.x - MU;
-                            if let Some(sensor) = wall_sensor.as_mut() {
-                                sensor.right = true;
-                            }
-                        }
-                        if col_vel.0.length() > MU {
-                            let mut angle_vec = Vec2::from_angle(angle);
-                            angle_vec = angle_vec * col_vel.0.abs();
-                            col_vel.0 -= angle_vec;
-                        }
-                    }
-                }
-                (_, _) => {
-                    error!("Unhandled wall coollison");
-                }
-            };
+        let mut to_target = path.waypoints[path.next] - pos.0;
+        if to_target.length() <= path.speed * config.time_step {
+            path.next = (path.next + 1) % path.waypoints.len();
+            to_target = path.waypoints[path.next] - pos.0;
+        }
+        // don't overshoot the waypoint on the last step
+        vel.0 = to_target.clamp_length_max(path.speed * config.time_step) / config.time_step;
+    }
+}
+
+// move anything standing on a moving wall along with it
+// the part of the walls velocity going into the rider is already handled by the wall casting
+pub fn carry_riders(
+    config: Res<PhysicsConfig>,
+    wall_query: Query<&Velocity, With<Wall>>,
+    mut rider_query: Query<(&mut Position, &WallSensor), Without<Wall>>
+) {
+    for (mut pos, sensor) in rider_query.iter_mut() {
+        if let Some(wall_vel) = sensor.ground.and_then(|ground| wall_query.get(ground).ok()) {
+            let along_ground = wall_vel.0 - sensor.ground_normal * wall_vel.0.dot(sensor.ground_normal);
+            pos.0 += along_ground * config.time_step;
         }
-    } 
+    }
 }
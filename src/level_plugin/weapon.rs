@@ -1,7 +1,7 @@
 use bevy::prelude::*;
 use bevy::utils::Duration;
 
-use super::{physics::*, walls::WallSensor};
+use super::physics::*;
 
 
 const HAMMER_COLOR: Color = Color::rgb(0.5, 0.5, 0.5);
@@ -15,7 +15,7 @@ pub struct Hammer;
 #[derive(Default, Component)]
 pub struct HammerShaft;
 
-#[derive(Default, Component)]
+#[derive(Default, Component, Clone)]
 pub struct HammerTimer(Timer);
 
 #[derive(Default, Bundle)]
@@ -34,7 +34,10 @@ pub struct HammerBundle {
     pub hammer: Hammer,
     pub sprite_bundle: SpriteBundle,
     pub body: Body,
-    pub collider: Collider
+    pub collider: Collider,
+    pub collision_groups: CollisionGroups,
+    // the head swings fast enough to skip over thin enemies
+    pub ccd: Ccd
 }
 
 impl HammerBundle {
@@ -85,6 +88,7 @@ impl HammerBundle {
                 position: Position(Vec2 {x: 0.0, y: (shaft_size.y / 2.0) + (head_size.y / 2.0)}),
                 ..default()
             },
+            collision_groups: CollisionGroups::new(PLAYER_WEAPON_LAYER, ENEMY_LAYER),
             ..default()
         })
     }